@@ -1,15 +1,12 @@
 use std::{
-    fmt::{Display, Formatter, Result as FmtResult},
-    ops::{Index, IndexMut},
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{Debug, Display, Formatter, Result as FmtResult, Write},
+    hash::Hash,
+    num::Wrapping,
+    ops::{BitAnd, BitOr, Index, IndexMut},
     str::FromStr,
 };
 
-use regex::Regex;
-
-lazy_static! {
-    static ref RGX: Regex = Regex::new("#ip (\\d+)").unwrap();
-}
-
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Opcode {
     Addr,
@@ -30,108 +27,298 @@ pub enum Opcode {
     Eqrr,
 }
 
+pub const ALL_OPCODES: &[Opcode] = &[
+    Opcode::Addr,
+    Opcode::Addi,
+    Opcode::Mulr,
+    Opcode::Muli,
+    Opcode::Banr,
+    Opcode::Bani,
+    Opcode::Borr,
+    Opcode::Bori,
+    Opcode::Setr,
+    Opcode::Seti,
+    Opcode::Gtir,
+    Opcode::Gtri,
+    Opcode::Gtrr,
+    Opcode::Eqir,
+    Opcode::Eqri,
+    Opcode::Eqrr,
+];
+
+/// How an instruction interprets one of its two inputs
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operand {
+    Register,
+    Immediate,
+    Ignored,
+}
+
+impl Opcode {
+    /// The mnemonic used for this opcode in program source
+    pub fn name(self) -> &'static str {
+        match self {
+            Opcode::Addr => "addr",
+            Opcode::Addi => "addi",
+            Opcode::Mulr => "mulr",
+            Opcode::Muli => "muli",
+            Opcode::Banr => "banr",
+            Opcode::Bani => "bani",
+            Opcode::Borr => "borr",
+            Opcode::Bori => "bori",
+            Opcode::Setr => "setr",
+            Opcode::Seti => "seti",
+            Opcode::Gtir => "gtir",
+            Opcode::Gtri => "gtri",
+            Opcode::Gtrr => "gtrr",
+            Opcode::Eqir => "eqir",
+            Opcode::Eqri => "eqri",
+            Opcode::Eqrr => "eqrr",
+        }
+    }
+
+    /// How the `in1` and `in2` operands of this opcode are interpreted.  `out` is always a
+    /// register.
+    pub fn operands(self) -> (Operand, Operand) {
+        use self::Operand::*;
+
+        match self {
+            Opcode::Addr | Opcode::Mulr | Opcode::Banr | Opcode::Borr => (Register, Register),
+            Opcode::Addi | Opcode::Muli | Opcode::Bani | Opcode::Bori => (Register, Immediate),
+            Opcode::Setr => (Register, Ignored),
+            Opcode::Seti => (Immediate, Ignored),
+            Opcode::Gtir | Opcode::Eqir => (Immediate, Register),
+            Opcode::Gtri | Opcode::Eqri => (Register, Immediate),
+            Opcode::Gtrr | Opcode::Eqrr => (Register, Register),
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}", self.name()) }
+}
+
 impl FromStr for Opcode {
-    type Err = !;
-
-    fn from_str(s: &str) -> Result<Self, !> {
-        let opcode: Self = match s {
-            "addr" => Opcode::Addr,
-            "addi" => Opcode::Addi,
-            "mulr" => Opcode::Mulr,
-            "muli" => Opcode::Muli,
-            "banr" => Opcode::Banr,
-            "bani" => Opcode::Bani,
-            "borr" => Opcode::Borr,
-            "bori" => Opcode::Bori,
-            "setr" => Opcode::Setr,
-            "seti" => Opcode::Seti,
-            "gtir" => Opcode::Gtir,
-            "gtri" => Opcode::Gtri,
-            "gtrr" => Opcode::Gtrr,
-            "eqir" => Opcode::Eqir,
-            "eqri" => Opcode::Eqri,
-            "eqrr" => Opcode::Eqrr,
-            _ => panic!("Invalid opcode: {}", s),
-        };
-        Ok(opcode)
+    type Err = VmError;
+
+    fn from_str(s: &str) -> Result<Self, VmError> {
+        ALL_OPCODES
+            .iter()
+            .cloned()
+            .find(|opcode| opcode.name() == s)
+            .ok_or_else(|| VmError::BadOpcode(s.to_owned()))
     }
 }
 
-const REGISTER_COUNT: usize = 6;
+/// Everything that can go wrong while loading or running a program
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum VmError {
+    /// An instruction refers to a register that doesn't exist.
+    BadRegister(usize),
+    /// An opcode's name wasn't recognized.
+    BadOpcode(String),
+    /// The instruction pointer doesn't fit in its register, or its register holds a value that
+    /// doesn't fit in a `usize`.
+    IpOutOfRange,
+    /// The result of an instruction doesn't fit in a register, or an immediate operand doesn't fit
+    /// in one to begin with.
+    Overflow,
+    /// The program was still running after executing the given number of steps.
+    StepLimitExceeded(usize),
+}
+
+impl Display for VmError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            VmError::BadRegister(reg) => write!(fmt, "Invalid register: {}", reg),
+            VmError::BadOpcode(name) => write!(fmt, "Invalid opcode: {}", name),
+            VmError::IpOutOfRange => write!(fmt, "Instruction pointer out of range"),
+            VmError::Overflow => write!(fmt, "Arithmetic overflow"),
+            VmError::StepLimitExceeded(steps) => write!(fmt, "Still running after {} steps", steps),
+        }
+    }
+}
+
+/// The number of registers available to programs in the source format of days 19 and 21
+pub const REGISTER_COUNT: usize = 6;
+
+/// An unsigned integer type that registers can hold.  Plain integer types report arithmetic that
+/// overflows them as an error, while `Wrapping` ones silently wrap around.
+pub trait Word:
+    Copy + Eq + Ord + Hash + Default + Debug + Display + BitAnd<Output = Self> + BitOr<Output = Self>
+{
+    /// Converts an immediate operand or instruction pointer, returning `None` if it doesn't fit.
+    fn from_usize(n: usize) -> Option<Self>;
+    fn to_usize(self) -> Option<usize>;
+    fn try_add(self, other: Self) -> Option<Self>;
+    fn try_mul(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_word {
+    ($($ty:ident),*) => {
+        $(
+            impl Word for $ty {
+                fn from_usize(n: usize) -> Option<Self> {
+                    if n as u128 <= $ty::max_value() as u128 {
+                        Some(n as $ty)
+                    } else {
+                        None
+                    }
+                }
+
+                fn to_usize(self) -> Option<usize> {
+                    if self as u128 <= usize::max_value() as u128 {
+                        Some(self as usize)
+                    } else {
+                        None
+                    }
+                }
+
+                fn try_add(self, other: Self) -> Option<Self> { self.checked_add(other) }
+
+                fn try_mul(self, other: Self) -> Option<Self> { self.checked_mul(other) }
+            }
+
+            impl Word for Wrapping<$ty> {
+                // Only arithmetic wraps; an operand that doesn't fit is still an error
+                fn from_usize(n: usize) -> Option<Self> { $ty::from_usize(n).map(Wrapping) }
+
+                fn to_usize(self) -> Option<usize> { self.0.to_usize() }
+
+                fn try_add(self, other: Self) -> Option<Self> { Some(self + other) }
+
+                fn try_mul(self, other: Self) -> Option<Self> { Some(self * other) }
+            }
+        )*
+    };
+}
+
+impl_word!(u32, u64, usize);
+
+/// The storage for a fixed number of registers, each holding a `Word`.  Implemented for arrays of
+/// up to 8 words.
+pub trait RegisterFile:
+    Copy + Eq + Debug + AsRef<[<Self as RegisterFile>::Word]> + AsMut<[<Self as RegisterFile>::Word]>
+{
+    type Word: Word;
 
-pub fn parse_ip_decl(s: &str) -> Option<usize> {
-    RGX.captures(s).map(|cap| cap[1].parse().unwrap())
+    fn zeroed() -> Self;
+}
+
+macro_rules! impl_register_file {
+    ($($count:expr),*) => {
+        $(
+            impl<W: Word> RegisterFile for [W; $count] {
+                type Word = W;
+
+                fn zeroed() -> Self { [W::default(); $count] }
+            }
+        )*
+    };
 }
 
+impl_register_file!(1, 2, 3, 4, 5, 6, 7, 8);
+
+/// The registers of days 19 and 21 by default
 #[derive(Clone, Debug)]
-pub struct Registers {
+pub struct Registers<R: RegisterFile = [usize; REGISTER_COUNT]> {
     pub ip_register: Option<usize>,
-    pub regs: [usize; REGISTER_COUNT],
+    pub regs: R,
 }
 
-impl Index<usize> for Registers {
-    type Output = usize;
+impl<R: RegisterFile> Index<usize> for Registers<R> {
+    type Output = R::Word;
 
-    fn index(&self, i: usize) -> &usize { &self.regs[i] }
+    fn index(&self, i: usize) -> &R::Word { &self.regs.as_ref()[i] }
 }
 
-impl IndexMut<usize> for Registers {
-    fn index_mut(&mut self, i: usize) -> &mut usize { &mut self.regs[i] }
+impl<R: RegisterFile> IndexMut<usize> for Registers<R> {
+    fn index_mut(&mut self, i: usize) -> &mut R::Word { &mut self.regs.as_mut()[i] }
 }
 
-impl Display for Registers {
+impl<R: RegisterFile> Display for Registers<R> {
     fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
-        write!(
-            fmt,
-            "[{} {} {} {} {} {}]",
-            self.regs[0], self.regs[1], self.regs[2], self.regs[3], self.regs[4], self.regs[5]
-        )
+        write!(fmt, "[")?;
+        for (i, value) in self.regs.as_ref().iter().enumerate() {
+            if i > 0 {
+                write!(fmt, " ")?;
+            }
+            write!(fmt, "{}", value)?;
+        }
+        write!(fmt, "]")
     }
 }
 
-impl Registers {
+impl<R: RegisterFile> Registers<R> {
     pub fn new(ip_register: Option<usize>) -> Self {
         Registers {
             ip_register,
-            regs: [0; REGISTER_COUNT],
+            regs: R::zeroed(),
         }
     }
 
+    pub fn count(&self) -> usize { self.regs.as_ref().len() }
+
+    /// Like indexing, but fails instead of panicking if the register doesn't exist.
+    pub fn get(&self, reg: usize) -> Result<R::Word, VmError> {
+        self.regs
+            .as_ref()
+            .get(reg)
+            .cloned()
+            .ok_or(VmError::BadRegister(reg))
+    }
+
+    pub fn set(&mut self, reg: usize, value: R::Word) -> Result<(), VmError> {
+        *self
+            .regs
+            .as_mut()
+            .get_mut(reg)
+            .ok_or(VmError::BadRegister(reg))? = value;
+        Ok(())
+    }
+
     /// Runs a single instruction, mutating the register values and returning the new instruction
-    /// pointer.
-    pub fn exec(&mut self, ip: usize, instr: Instruction) -> usize {
+    /// pointer.  If it fails, only the instruction pointer's register has been changed.
+    pub fn exec(&mut self, ip: usize, instr: Instruction) -> Result<usize, VmError> {
         if let Some(ip_register) = self.ip_register {
-            self.regs[ip_register] = ip;
-        }
-
-        self.regs[instr.out] = match instr.opcode {
-            Opcode::Addr => self.regs[instr.in1] + self.regs[instr.in2],
-            Opcode::Addi => self.regs[instr.in1] + instr.in2,
-            Opcode::Mulr => self.regs[instr.in1] * self.regs[instr.in2],
-            Opcode::Muli => self.regs[instr.in1] * instr.in2,
-            Opcode::Banr => self.regs[instr.in1] & self.regs[instr.in2],
-            Opcode::Bani => self.regs[instr.in1] & instr.in2,
-            Opcode::Borr => self.regs[instr.in1] | self.regs[instr.in2],
-            Opcode::Bori => self.regs[instr.in1] | instr.in2,
-            Opcode::Setr => self.regs[instr.in1],
-            Opcode::Seti => instr.in1,
-            Opcode::Gtir => (instr.in1 > self.regs[instr.in2]) as usize,
-            Opcode::Gtri => (self.regs[instr.in1] > instr.in2) as usize,
-            Opcode::Gtrr => (self.regs[instr.in1] > self.regs[instr.in2]) as usize,
-            Opcode::Eqir => (instr.in1 == self.regs[instr.in2]) as usize,
-            Opcode::Eqri => (self.regs[instr.in1] == instr.in2) as usize,
-            Opcode::Eqrr => (self.regs[instr.in1] == self.regs[instr.in2]) as usize,
+            let ip = R::Word::from_usize(ip).ok_or(VmError::IpOutOfRange)?;
+            self.set(ip_register, ip)?;
+        }
+
+        let reg = |n: usize| self.get(n);
+        let imm = |n: usize| R::Word::from_usize(n).ok_or(VmError::Overflow);
+        let checked = |value: Option<R::Word>| value.ok_or(VmError::Overflow);
+        let flag = |condition: bool| imm(condition as usize);
+        let (a, b) = (instr.in1, instr.in2);
+
+        let value = match instr.opcode {
+            Opcode::Addr => checked(reg(a)?.try_add(reg(b)?))?,
+            Opcode::Addi => checked(reg(a)?.try_add(imm(b)?))?,
+            Opcode::Mulr => checked(reg(a)?.try_mul(reg(b)?))?,
+            Opcode::Muli => checked(reg(a)?.try_mul(imm(b)?))?,
+            Opcode::Banr => reg(a)? & reg(b)?,
+            Opcode::Bani => reg(a)? & imm(b)?,
+            Opcode::Borr => reg(a)? | reg(b)?,
+            Opcode::Bori => reg(a)? | imm(b)?,
+            Opcode::Setr => reg(a)?,
+            Opcode::Seti => imm(a)?,
+            Opcode::Gtir => flag(imm(a)? > reg(b)?)?,
+            Opcode::Gtri => flag(reg(a)? > imm(b)?)?,
+            Opcode::Gtrr => flag(reg(a)? > reg(b)?)?,
+            Opcode::Eqir => flag(imm(a)? == reg(b)?)?,
+            Opcode::Eqri => flag(reg(a)? == imm(b)?)?,
+            Opcode::Eqrr => flag(reg(a)? == reg(b)?)?,
         };
+        self.set(instr.out, value)?;
 
-        if let Some(ip_register) = self.ip_register {
-            return self.regs[ip_register];
-        } else {
-            return ip;
+        match self.ip_register {
+            Some(ip_register) => self[ip_register].to_usize().ok_or(VmError::IpOutOfRange),
+            None => Ok(ip),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Instruction {
     pub opcode: Opcode,
     pub in1: usize,
@@ -143,16 +330,143 @@ impl Display for Instruction {
     fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
         write!(
             fmt,
-            "{:?} {} {} {}",
+            "{} {} {} {}",
             self.opcode, self.in1, self.in2, self.out
         )
     }
 }
 
+/// A super-instruction standing in for a whole loop, as found by `optimizer::optimize`.  Running
+/// one leaves the registers exactly as the loop would have when exiting to `exit`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fused {
+    /// `do { if factor * counter == target { acc += factor } counter += 1 } while counter <=
+    /// target`
+    DivisorStep {
+        factor: usize,
+        counter: usize,
+        target: usize,
+        acc: usize,
+        flag: usize,
+        exit: usize,
+    },
+    /// `do { inner = 1; <DivisorStep> counter += 1 } while counter <= target`, which adds all
+    /// divisors of `target` that are at least `counter` to `acc`
+    DivisorSum {
+        counter: usize,
+        inner: usize,
+        target: usize,
+        acc: usize,
+        flag: usize,
+        exit: usize,
+    },
+    /// `while (quotient + 1) * divisor <= dividend { quotient += 1 }`, where `divisor` is an
+    /// immediate
+    Divide {
+        quotient: usize,
+        dividend: usize,
+        divisor: usize,
+        flag: usize,
+        exit: usize,
+    },
+}
+
+impl Fused {
+    /// Applies the effects of the whole loop to `regs` and returns the new instruction pointer.
+    /// Only the values the loop leaves in registers are checked for overflow, which are computed
+    /// with `usize` arithmetic even for words that wrap around.
+    pub fn exec<R: RegisterFile>(self, regs: &mut Registers<R>) -> Result<usize, VmError> {
+        let get =
+            |regs: &Registers<R>, reg: usize| regs.get(reg)?.to_usize().ok_or(VmError::Overflow);
+        let set = |regs: &mut Registers<R>, reg: usize, value: Option<usize>| {
+            let value = value.and_then(R::Word::from_usize);
+            regs.set(reg, value.ok_or(VmError::Overflow)?)
+        };
+
+        let exit = match self {
+            Fused::DivisorStep {
+                factor,
+                counter,
+                target,
+                acc,
+                flag,
+                exit,
+            } => {
+                let factor_value = get(regs, factor)?;
+                let (first, target) = (get(regs, counter)?, get(regs, target)?);
+                let last = first.max(target);
+                if factor_value != 0 && target % factor_value == 0 {
+                    let quotient = target / factor_value;
+                    if first <= quotient && quotient <= last {
+                        let sum = get(regs, acc)?.checked_add(factor_value);
+                        set(regs, acc, sum)?;
+                    }
+                }
+
+                set(regs, counter, last.checked_add(1))?;
+                set(regs, flag, Some(1))?;
+                exit
+            },
+            Fused::DivisorSum {
+                counter,
+                inner,
+                target,
+                acc,
+                flag,
+                exit,
+            } => {
+                let (first, target) = (get(regs, counter)?, get(regs, target)?);
+                let mut sum = 0;
+                let mut i = 1;
+                while i * i <= target {
+                    if target % i == 0 {
+                        let j = target / i;
+                        if i >= first {
+                            sum += i;
+                        }
+                        if j != i && j >= first {
+                            sum += j;
+                        }
+                    }
+                    i += 1;
+                }
+
+                let sum = get(regs, acc)?.checked_add(sum);
+                set(regs, acc, sum)?;
+                set(regs, counter, first.max(target).checked_add(1))?;
+                set(regs, inner, target.max(1).checked_add(1))?;
+                set(regs, flag, Some(1))?;
+                exit
+            },
+            Fused::Divide {
+                quotient,
+                dividend,
+                divisor,
+                flag,
+                exit,
+            } => {
+                let value = get(regs, quotient)?.max(get(regs, dividend)? / divisor);
+                set(regs, quotient, Some(value))?;
+                set(regs, flag, Some(1))?;
+                exit
+            },
+        };
+
+        // The loop leaves by jumping from the instruction right before `exit`
+        if let Some(ip_register) = regs.ip_register {
+            set(regs, ip_register, Some(exit - 1))?;
+        }
+        Ok(exit)
+    }
+}
+
 #[derive(Clone)]
 pub struct Instructions {
     pub ip: usize,
     pub instructions: Vec<Instruction>,
+    /// Super-instructions that replace the loops starting at each address.  Empty unless the
+    /// program has been optimized.
+    pub fused: Vec<Option<Fused>>,
 }
 
 impl Index<usize> for Instructions {
@@ -165,37 +479,871 @@ impl IndexMut<usize> for Instructions {
     fn index_mut(&mut self, i: usize) -> &mut Instruction { &mut self.instructions[i] }
 }
 
+/// Execution statistics collected by a VM with profiling enabled
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    /// The number of times each instruction was executed, indexed by instruction pointer
+    pub hits: Vec<usize>,
+    /// The number of times each register was written to by an instruction, not counting the
+    /// instruction pointer being written to its bound register before every instruction
+    pub register_writes: Vec<usize>,
+    /// The number of times control flow went from the first instruction to the second, for all
+    /// transfers other than falling through to the next instruction
+    pub jumps: HashMap<(usize, usize), usize>,
+}
+
+impl Profile {
+    pub fn new(instruction_count: usize, register_count: usize) -> Self {
+        Profile {
+            hits: vec![0; instruction_count],
+            register_writes: vec![0; register_count],
+            ..Profile::default()
+        }
+    }
+
+    fn record(&mut self, ip: usize, written: Option<usize>, next_ip: usize) {
+        self.hits[ip] += 1;
+        if let Some(register) = written {
+            self.register_writes[register] += 1;
+        }
+        if next_ip != ip + 1 {
+            *self.jumps.entry((ip, next_ip)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn total_executed(&self) -> usize { self.hits.iter().sum() }
+
+    /// Jumps that go backwards (or to themselves), which is how loops are made.  Sorted by the
+    /// number of times they were taken, most frequent first.
+    pub fn back_edges(&self) -> Vec<((usize, usize), usize)> {
+        let mut back_edges: Vec<_> = self
+            .jumps
+            .iter()
+            .filter(|&(&(from, to), _)| to <= from)
+            .map(|(&edge, &count)| (edge, count))
+            .collect();
+        back_edges.sort_by_key(|&(edge, count)| (!count, edge));
+        back_edges
+    }
+
+    /// Renders a human-readable report listing the `top` most executed instructions, register
+    /// write counts and loop back-edges.
+    pub fn report(&self, instructions: &[Instruction], top: usize) -> String {
+        let total = self.total_executed();
+        let mut out = String::new();
+        writeln!(out, "Executed {} instructions", total).unwrap();
+
+        let mut hottest: Vec<usize> = (0..self.hits.len()).filter(|&i| self.hits[i] > 0).collect();
+        hottest.sort_by_key(|&i| (!self.hits[i], i));
+        writeln!(out, "Hottest instructions:").unwrap();
+        for &i in hottest.iter().take(top) {
+            writeln!(
+                out,
+                "  {:>3}: {:<16} {:>12} ({:.2}%)",
+                i,
+                instructions[i].to_string(),
+                self.hits[i],
+                self.hits[i] as f64 * 100. / total as f64
+            )
+            .unwrap();
+        }
+
+        write!(out, "Register writes:").unwrap();
+        for (register, writes) in self.register_writes.iter().enumerate() {
+            write!(out, " r{}={}", register, writes).unwrap();
+        }
+        writeln!(out).unwrap();
+
+        writeln!(out, "Loop back-edges:").unwrap();
+        for ((from, to), count) in self.back_edges() {
+            writeln!(out, "  {:>3} -> {:<3} {:>12}", from, to, count).unwrap();
+        }
+
+        out
+    }
+}
+
+/// What a single tick changed, so that it can be undone
+#[derive(Clone, Debug)]
+struct Delta<W> {
+    /// The instruction pointer before the tick
+    ip: usize,
+    /// The registers that the tick changed along with their old values
+    changed: Vec<(usize, W)>,
+}
+
+/// The most recent ticks executed by a VM with history enabled, oldest first.  Once `capacity`
+/// ticks have been recorded, the oldest ones are forgotten.
+#[derive(Clone, Debug)]
+pub struct History<W> {
+    capacity: usize,
+    deltas: VecDeque<Delta<W>>,
+}
+
+impl<W: Word> History<W> {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The number of ticks that can currently be undone
+    pub fn len(&self) -> usize { self.deltas.len() }
+
+    pub fn is_empty(&self) -> bool { self.deltas.is_empty() }
+
+    fn record(&mut self, ip: usize, before: &[W], after: &[W]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+
+        let changed = (0..before.len())
+            .filter(|&reg| before[reg] != after[reg])
+            .map(|reg| (reg, before[reg]))
+            .collect();
+        self.deltas.push_back(Delta { ip, changed });
+    }
+}
+
+/// Runs programs on the registers of days 19 and 21 by default
 #[derive(Clone)]
-pub struct VM {
-    pub regs: Registers,
+pub struct VM<R: RegisterFile = [usize; REGISTER_COUNT]> {
+    pub regs: Registers<R>,
     pub instructions: Instructions,
+    /// Collected while the VM runs if set; see `enable_profiling`
+    pub profile: Option<Profile>,
+    /// Recorded while the VM runs if set; see `enable_history`
+    pub history: Option<History<R::Word>>,
 }
 
-impl VM {
-    /// Executes the next instruction in the program.  Handles incrementing the IP and jumping.
-    /// Returns `true` if the program has halted and `false` otherwise.
-    pub fn tick(&mut self) -> bool {
+impl<R: RegisterFile> VM<R> {
+    /// Creates a VM with all registers set to zero that is about to run the first instruction of
+    /// `program`.  Instructions that use registers beyond the ones the VM has fail once they're
+    /// executed.
+    pub fn new(program: &Program) -> Self {
+        VM {
+            regs: Registers::new(program.ip_register),
+            instructions: Instructions {
+                ip: 0,
+                instructions: program.instructions.clone(),
+                fused: Vec::new(),
+            },
+            profile: None,
+            history: None,
+        }
+    }
+
+    /// Starts recording a profile of all instructions executed from now on, which is printed once
+    /// `run` finishes.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::new(
+            self.instructions.instructions.len(),
+            self.regs.count(),
+        ));
+    }
+
+    /// Starts recording the changes made by the next `capacity` ticks so that they can be undone
+    /// with `step_back`.
+    pub fn enable_history(&mut self, capacity: usize) {
+        self.history = Some(History::new(capacity));
+    }
+
+    /// Undoes the most recent tick recorded in the history, restoring the registers and
+    /// instruction pointer to what they were before it.  The profile isn't rewound.  Returns
+    /// `false` if there's nothing left to undo.
+    pub fn step_back(&mut self) -> bool {
+        let delta = match self
+            .history
+            .as_mut()
+            .and_then(|history| history.deltas.pop_back())
+        {
+            Some(delta) => delta,
+            None => return false,
+        };
+
+        for (reg, old) in delta.changed {
+            self.regs[reg] = old;
+        }
+        self.instructions.ip = delta.ip;
+        true
+    }
+
+    /// Executes the next instruction in the program, or the whole loop starting at it if the
+    /// program has been optimized.  Handles incrementing the IP and jumping.  Returns `true` if
+    /// the program has halted and `false` otherwise.  If the instruction fails, the IP is left
+    /// pointing at it.
+    pub fn tick(&mut self) -> Result<bool, VmError> {
         let &cur_instr = match self.instructions.instructions.get(self.ip()) {
             Some(instr) => instr,
-            None => return true,
+            None => return Ok(true),
         };
         let ip = self.ip();
+        let before = self.history.as_ref().map(|_| self.regs.regs);
 
-        self.instructions.ip = self.regs.exec(ip, cur_instr);
-        self.instructions.ip += 1;
+        let written = match self.instructions.fused.get(ip) {
+            Some(&Some(fused)) => {
+                self.instructions.ip = fused.exec(&mut self.regs)?;
+                None
+            },
+            _ => {
+                self.instructions.ip = self.regs.exec(ip, cur_instr)?.saturating_add(1);
+                Some(cur_instr.out)
+            },
+        };
 
-        false
+        if let Some(ref mut profile) = self.profile {
+            profile.record(ip, written, self.instructions.ip);
+        }
+        if let (Some(history), Some(before)) = (self.history.as_mut(), before) {
+            history.record(ip, before.as_ref(), self.regs.regs.as_ref());
+        }
+
+        Ok(false)
     }
 
-    /// Runs the VM until it halts.
-    pub fn run(&mut self) {
-        loop {
-            let should_halt = self.tick();
-            if should_halt {
-                break;
+    /// Runs the VM until it halts or an instruction fails.  If profiling is enabled, prints the
+    /// profile to stderr afterwards.
+    pub fn run(&mut self) -> Result<(), VmError> {
+        self.run_with_limit(usize::max_value()).map(|_| ())
+    }
+
+    /// Like `run`, but gives up with `VmError::StepLimitExceeded` if the program hasn't halted
+    /// after `max_steps` calls to `tick`.  Returns the number of steps it took to halt.
+    pub fn run_with_limit(&mut self, max_steps: usize) -> Result<usize, VmError> {
+        let mut steps = 0;
+        let result = loop {
+            if self.is_halted() {
+                break Ok(steps);
+            }
+            if steps == max_steps {
+                break Err(VmError::StepLimitExceeded(max_steps));
             }
+            if let Err(err) = self.tick() {
+                break Err(err);
+            }
+            steps += 1;
+        };
+
+        if let Some(ref profile) = self.profile {
+            eprint!("{}", profile.report(&self.instructions.instructions, 10));
         }
+
+        result
     }
 
     pub fn ip(&self) -> usize { self.instructions.ip }
+
+    /// Captures the registers, instruction pointer and program.  Fails if a register holds a
+    /// value that doesn't fit in a `usize`.
+    pub fn snapshot(&self) -> Result<Snapshot, VmError> {
+        Ok(Snapshot {
+            program: Program {
+                ip_register: self.regs.ip_register,
+                instructions: self.instructions.instructions.clone(),
+            },
+            ip: self.ip(),
+            regs: self
+                .regs
+                .regs
+                .as_ref()
+                .iter()
+                .map(|value| value.to_usize().ok_or(VmError::Overflow))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Creates a VM in the state captured by `snapshot`, without profiling or history.  The
+    /// snapshot must have exactly as many registers as the VM.
+    pub fn restore(snapshot: &Snapshot) -> Result<Self, VmError> {
+        let mut vm = VM::new(&snapshot.program);
+        let count = vm.regs.count();
+        if snapshot.regs.len() != count {
+            // The first register that's missing from one side or the other
+            return Err(VmError::BadRegister(snapshot.regs.len().min(count)));
+        }
+        for (reg, &value) in snapshot.regs.iter().enumerate() {
+            let value = R::Word::from_usize(value).ok_or(VmError::Overflow)?;
+            vm.regs.set(reg, value)?;
+        }
+        vm.instructions.ip = snapshot.ip;
+
+        Ok(vm)
+    }
+
+    pub fn is_halted(&self) -> bool { self.ip() >= self.instructions.instructions.len() }
+}
+
+/// An observation of an instruction whose opcode is only known by its number being executed, as
+/// in day 16
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sample<R: RegisterFile> {
+    pub before: R,
+    pub opcode: u8,
+    pub in1: usize,
+    pub in2: usize,
+    pub out: usize,
+    pub after: R,
+}
+
+impl<R: RegisterFile> Sample<R> {
+    /// All opcodes that turn the registers from `before` into `after`
+    pub fn matching_opcodes(&self) -> Vec<Opcode> {
+        ALL_OPCODES
+            .iter()
+            .cloned()
+            .filter(|&opcode| {
+                let mut regs = Registers {
+                    ip_register: None,
+                    regs: self.before,
+                };
+                let instr = Instruction {
+                    opcode,
+                    in1: self.in1,
+                    in2: self.in2,
+                    out: self.out,
+                };
+                regs.exec(0, instr).is_ok() && regs.regs == self.after
+            })
+            .collect()
+    }
+}
+
+/// Why the opcode numbers couldn't be worked out from a set of samples
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct InferenceReport {
+    /// The indices of samples that no opcode could have produced
+    pub unexplained: Vec<usize>,
+    /// Numbers that no opcode is consistent with, either because of their own samples or
+    /// because all the opcodes that are belong to other numbers
+    pub contradictory: Vec<u8>,
+    /// Numbers that could still be any of several opcodes
+    pub ambiguous: BTreeMap<u8, Vec<Opcode>>,
+    /// Two numbers that both turned out to stand for the same opcode.  Inference stops as soon as
+    /// this happens, so the other fields may be incomplete.
+    pub conflict: Option<(Opcode, [u8; 2])>,
+}
+
+impl Display for InferenceReport {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        if !self.unexplained.is_empty() {
+            writeln!(fmt, "Samples matching no opcode: {:?}", self.unexplained)?;
+        }
+        if !self.contradictory.is_empty() {
+            writeln!(fmt, "Numbers matching no opcode: {:?}", self.contradictory)?;
+        }
+        if let Some((opcode, [first, second])) = self.conflict {
+            writeln!(fmt, "{} and {} both stand for {}", first, second, opcode)?;
+        }
+        for (number, candidates) in &self.ambiguous {
+            let names: Vec<_> = candidates.iter().map(|opcode| opcode.name()).collect();
+            writeln!(fmt, "{} could be any of {}", number, names.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Works out which opcode each number that appears in `samples` stands for.  Each number must be
+/// consistent with all of its samples, and no two numbers can stand for the same opcode.
+pub fn infer_opcodes<R: RegisterFile>(
+    samples: &[Sample<R>],
+) -> Result<HashMap<u8, Opcode>, InferenceReport> {
+    let mut report = InferenceReport::default();
+    let mut candidates: BTreeMap<u8, Vec<Opcode>> = BTreeMap::new();
+
+    for (i, sample) in samples.iter().enumerate() {
+        let matching = sample.matching_opcodes();
+        if matching.is_empty() {
+            report.unexplained.push(i);
+        }
+
+        candidates
+            .entry(sample.opcode)
+            .and_modify(|opcodes| opcodes.retain(|opcode| matching.contains(opcode)))
+            .or_insert(matching);
+    }
+
+    // Any number with only one candidate left rules it out for all the others
+    let mut known: HashMap<u8, Opcode> = HashMap::new();
+    loop {
+        let newly_known: Vec<(u8, Opcode)> = candidates
+            .iter()
+            .filter(|&(number, opcodes)| opcodes.len() == 1 && !known.contains_key(number))
+            .map(|(&number, opcodes)| (number, opcodes[0]))
+            .collect();
+        if newly_known.is_empty() {
+            break;
+        }
+
+        for (number, opcode) in newly_known {
+            if let Some((&other, _)) = known.iter().find(|&(_, &known)| known == opcode) {
+                report.conflict = Some((opcode, [other, number]));
+                return Err(report);
+            }
+            known.insert(number, opcode);
+            for (_, opcodes) in candidates.iter_mut().filter(|&(&other, _)| other != number) {
+                opcodes.retain(|&other| other != opcode);
+            }
+        }
+    }
+
+    for (number, opcodes) in candidates {
+        match opcodes.len() {
+            0 => report.contradictory.push(number),
+            1 => (),
+            _ => {
+                report.ambiguous.insert(number, opcodes);
+            },
+        }
+    }
+
+    if report == InferenceReport::default() {
+        Ok(known)
+    } else {
+        Err(report)
+    }
+}
+
+/// A program in the source format of days 19 and 21: an optional `#ip <register>` directive
+/// followed by one instruction per line.  When parsing, `;` starts a comment that runs until the
+/// end of the line and instructions may be prefixed by a label like `12:`, which is ignored.
+///
+/// Parsing with `FromStr` only accepts the `REGISTER_COUNT` registers of days 19 and 21; use
+/// `parse_with_register_count` for VMs with a different `RegisterFile`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Program {
+    pub ip_register: Option<usize>,
+    pub instructions: Vec<Instruction>,
+}
+
+/// An error encountered while parsing a `Program`.  Lines and columns start at 1.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Splits `line` into whitespace-separated tokens, each paired with its column.
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut token_start = None;
+
+    for (i, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+        match (token_start, c.is_whitespace()) {
+            (None, false) => token_start = Some(i),
+            (Some(start), true) => {
+                tokens.push((line[..start].chars().count() + 1, &line[start..i]));
+                token_start = None;
+            },
+            _ => (),
+        }
+    }
+
+    tokens
+}
+
+fn is_label(token: &str) -> bool {
+    token.ends_with(':')
+        && token.len() > 1
+        && token[..token.len() - 1].bytes().all(|b| b.is_ascii_digit())
+}
+
+impl Program {
+    /// Parses a program whose instructions may use registers `0..register_count`, such as
+    /// `Registers::<R>::new(None).count()` for a VM over `R`.
+    pub fn parse_with_register_count(s: &str, register_count: usize) -> Result<Self, ParseError> {
+        let mut program = Program {
+            ip_register: None,
+            instructions: Vec::new(),
+        };
+
+        for (line_ix, line) in s.lines().enumerate() {
+            let code = line.split(';').next().unwrap();
+            let err = |column: usize, message: String| ParseError {
+                line: line_ix + 1,
+                column,
+                message,
+            };
+            let end_column = code.trim_end().chars().count() + 1;
+            let parse_number = |&(column, token): &(usize, &str), is_register: bool| {
+                let n: usize = token
+                    .parse()
+                    .map_err(|_| err(column, format!("Expected a number, found `{}`", token)))?;
+                if is_register && n >= register_count {
+                    return Err(err(column, format!("Invalid register: {}", n)));
+                }
+                Ok(n)
+            };
+
+            let mut tokens = tokenize(code);
+            if tokens.first().map(|&(_, token)| is_label(token)) == Some(true) {
+                tokens.remove(0);
+            }
+
+            match tokens.first() {
+                None => continue,
+                Some(&(column, "#ip")) => {
+                    if program.ip_register.is_some() || !program.instructions.is_empty() {
+                        return Err(err(
+                            column,
+                            "`#ip` must come once, before any instructions".to_owned(),
+                        ));
+                    }
+
+                    match tokens.get(1) {
+                        Some(token) => program.ip_register = Some(parse_number(token, true)?),
+                        None => return Err(err(end_column, "Expected a register".to_owned())),
+                    }
+                    if let Some(&(column, token)) = tokens.get(2) {
+                        return Err(err(column, format!("Unexpected `{}`", token)));
+                    }
+                },
+                Some(&(column, opcode)) => {
+                    let opcode: Opcode = opcode
+                        .parse()
+                        .map_err(|opcode_err: VmError| err(column, opcode_err.to_string()))?;
+                    if tokens.len() < 4 {
+                        return Err(err(end_column, "Expected 3 operands".to_owned()));
+                    }
+                    if let Some(&(column, token)) = tokens.get(4) {
+                        return Err(err(column, format!("Unexpected `{}`", token)));
+                    }
+
+                    let (in1, in2) = opcode.operands();
+                    program.instructions.push(Instruction {
+                        opcode,
+                        in1: parse_number(&tokens[1], in1 == Operand::Register)?,
+                        in2: parse_number(&tokens[2], in2 == Operand::Register)?,
+                        out: parse_number(&tokens[3], true)?,
+                    });
+                },
+            }
+        }
+
+        Ok(program)
+    }
+}
+
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        Program::parse_with_register_count(s, REGISTER_COUNT)
+    }
+}
+
+/// Prints the program in its canonical source form, which parses back into the same program.
+impl Display for Program {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        if let Some(ip_register) = self.ip_register {
+            writeln!(fmt, "#ip {}", ip_register)?;
+        }
+        for instr in &self.instructions {
+            writeln!(fmt, "{}", instr)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The state of a VM in a form that can be saved to a file.  Written as a `#state <ip>` line and a
+/// `#regs <value>...` line followed by the source of the program.  Super-instructions added by
+/// the optimizer aren't included.  The program may use as many registers as `#regs` lists.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+    pub program: Program,
+    pub ip: usize,
+    pub regs: Vec<usize>,
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        writeln!(fmt, "#state {}", self.ip)?;
+        write!(fmt, "#regs")?;
+        for value in &self.regs {
+            write!(fmt, " {}", value)?;
+        }
+        writeln!(fmt)?;
+        write!(fmt, "{}", self.program)
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let lines: Vec<&str> = s.lines().collect();
+        let header = |line_ix: usize, directive: &str| {
+            let line = lines.get(line_ix).cloned().unwrap_or("");
+            let tokens = tokenize(line);
+            let err = |column: usize, message: String| ParseError {
+                line: line_ix + 1,
+                column,
+                message,
+            };
+
+            match tokens.first() {
+                Some(&(_, token)) if token == directive => (),
+                _ => return Err(err(1, format!("Expected `{}`", directive))),
+            }
+            tokens[1..]
+                .iter()
+                .map(|&(column, token)| {
+                    token
+                        .parse()
+                        .map_err(|_| err(column, format!("Expected a number, found `{}`", token)))
+                })
+                .collect::<Result<Vec<usize>, _>>()
+        };
+
+        let ip = match header(0, "#state")?.as_slice() {
+            &[ip] => ip,
+            _ =>
+                return Err(ParseError {
+                    line: 1,
+                    column: 1,
+                    message: "Expected a single instruction pointer".to_owned(),
+                }),
+        };
+        let regs = header(1, "#regs")?;
+        let source = lines.get(2..).unwrap_or(&[]).join("\n");
+        let program =
+            Program::parse_with_register_count(&source, regs.len()).map_err(|err| ParseError {
+                line: err.line + 2,
+                ..err
+            })?;
+
+        Ok(Snapshot { program, ip, regs })
+    }
+}
+
+#[test]
+fn profiling() {
+    // Counts register 1 up to 3, then halts
+    let program: Program = "#ip 0\nseti 0 0 1\naddi 1 1 1\ngtri 1 2 2\naddr 0 2 0\nseti 0 0 0\n"
+        .parse()
+        .unwrap();
+    let mut vm: VM = VM::new(&program);
+    vm.enable_profiling();
+    vm.run().unwrap();
+
+    let profile = vm.profile.unwrap();
+    assert_eq!(profile.hits, vec![1, 3, 3, 3, 2]);
+    assert_eq!(profile.total_executed(), 12);
+    assert_eq!(profile.register_writes, [5, 4, 3, 0, 0, 0]);
+    assert_eq!(profile.back_edges(), vec![((4, 1), 2)]);
+    assert_eq!(profile.jumps[&(3, 5)], 1);
+}
+
+#[test]
+fn program_round_trip() {
+    for &source in &[
+        include_str!("../input/day19.txt"),
+        include_str!("../input/day21.txt"),
+    ] {
+        let program: Program = source.parse().unwrap();
+        assert_eq!(program.to_string(), format!("{}\n", source.trim_end()));
+        assert_eq!(program.to_string().parse(), Ok(program));
+    }
+
+    let annotated: Program = "#ip 1\n\n0: addi 1 16 1 ; jmp 17\n1: seti 1 1 3\n"
+        .parse()
+        .unwrap();
+    assert_eq!(annotated.to_string(), "#ip 1\naddi 1 16 1\nseti 1 1 3\n");
+}
+
+#[test]
+fn program_parse_errors() {
+    let error_at = |source: &str| {
+        let err = source.parse::<Program>().unwrap_err();
+        (err.line, err.column)
+    };
+
+    assert_eq!(error_at("#ip 1\naddi 1 2 3\n  fooo 1 2 3"), (3, 3));
+    assert_eq!(error_at("addi 1 x 3"), (1, 8));
+    assert_eq!(error_at("addi 1 2"), (1, 9));
+    assert_eq!(error_at("addi 1 2 3 4"), (1, 12));
+    assert_eq!(error_at("addr 1 9 3"), (1, 8));
+    assert_eq!(error_at("seti 1 1 3\n#ip 2"), (2, 1));
+
+    let err = Program::parse_with_register_count("addr 1 3 3\naddr 1 4 3", 4).unwrap_err();
+    assert_eq!((err.line, err.column), (2, 8));
+    assert!(Program::parse_with_register_count("#ip 7\naddr 6 7 7", 8).is_ok());
+}
+
+#[test]
+fn word_types() {
+    let program: Program = include_str!("../input/day21.txt").parse().unwrap();
+    // Multiplies a 24-bit number by 65899, but only keeps the low 24 bits of the result
+    let mut checked: VM<[u32; 6]> = VM::new(&program);
+    checked.regs[0] = 10_720_163;
+    assert_eq!(checked.run(), Err(VmError::Overflow));
+    assert_eq!(checked.instructions[checked.ip()].opcode, Opcode::Muli);
+
+    let mut wrapping: VM<[Wrapping<u32>; 6]> = VM::new(&program);
+    wrapping.regs[0] = Wrapping(10_720_163);
+    assert_eq!(wrapping.run(), Ok(()));
+    // Immediates that don't fit aren't silently truncated
+    let mut truncated: VM<[Wrapping<u32>; 6]> = VM::new(&"seti 4294967296 0 1".parse().unwrap());
+    assert_eq!(truncated.run(), Err(VmError::Overflow));
+
+    let program: Program = "addi 0 7 1\nmulr 1 1 2\nbori 2 1 3\n".parse().unwrap();
+    let mut small: VM<[u64; 4]> = VM::new(&program);
+    small.run().unwrap();
+    assert_eq!(small.regs.to_string(), "[0 7 49 49]");
+}
+
+#[test]
+fn vm_errors() {
+    // Jumps back to itself forever
+    let program: Program = "#ip 0\naddi 1 1 1\nseti 0 0 0\n".parse().unwrap();
+    let mut vm: VM = VM::new(&program);
+    assert_eq!(vm.run_with_limit(10), Err(VmError::StepLimitExceeded(10)));
+    assert_eq!(vm.ip(), 1);
+
+    let program: Program = "seti 5 0 1\naddi 1 1 1\n".parse().unwrap();
+    let mut vm: VM = VM::new(&program);
+    assert_eq!(vm.run_with_limit(2), Ok(2));
+    assert_eq!(vm.regs[1], 6);
+
+    let program: Program = "seti 5 0 1\naddr 1 4 2\n".parse().unwrap();
+    let mut small: VM<[usize; 4]> = VM::new(&program);
+    assert_eq!(small.run(), Err(VmError::BadRegister(4)));
+    assert_eq!(small.ip(), 1);
+
+    assert_eq!(
+        "addx".parse::<Opcode>(),
+        Err(VmError::BadOpcode("addx".to_owned()))
+    );
+}
+
+#[test]
+fn history_and_snapshots() {
+    let program: Program = include_str!("../input/day19.txt").parse().unwrap();
+    let mut vm: VM = VM::new(&program);
+    vm.enable_history(50);
+
+    let mut states = vec![(vm.ip(), vm.regs.regs)];
+    for _ in 0..100 {
+        assert_eq!(vm.tick(), Ok(false));
+        states.push((vm.ip(), vm.regs.regs));
+    }
+    assert_eq!(vm.history.as_ref().unwrap().len(), 50);
+
+    let snapshot = vm.snapshot().unwrap();
+    let restored: VM = VM::restore(&snapshot.to_string().parse().unwrap()).unwrap();
+    assert_eq!((restored.ip(), restored.regs.regs), states[100]);
+    assert_eq!(restored.instructions.instructions, program.instructions);
+
+    for expected in states[50..100].iter().rev() {
+        assert!(vm.step_back());
+        assert_eq!(&(vm.ip(), vm.regs.regs), expected);
+    }
+    assert!(!vm.step_back());
+
+    let err = "#state 1\n#regs 1 x\n".parse::<Snapshot>().unwrap_err();
+    assert_eq!((err.line, err.column), (2, 9));
+    let err = "#state 1\n#regs\naddi 1\n".parse::<Snapshot>().unwrap_err();
+    assert_eq!((err.line, err.column), (3, 7));
+    let err = "#state 0\n#regs 0 0 0\naddi 1 2 3\n"
+        .parse::<Snapshot>()
+        .unwrap_err();
+    assert_eq!((err.line, err.column), (3, 10));
+
+    // Snapshots of VMs with more or fewer registers than days 19 and 21 round-trip as well
+    let program = Program::parse_with_register_count("#ip 7\naddi 6 5 6\n", 8).unwrap();
+    let mut large: VM<[u32; 8]> = VM::new(&program);
+    large.run().unwrap();
+    let snapshot: Snapshot = large.snapshot().unwrap().to_string().parse().unwrap();
+    let restored: VM<[u32; 8]> = VM::restore(&snapshot).unwrap();
+    assert_eq!(
+        (restored.ip(), restored.regs.regs),
+        (large.ip(), large.regs.regs)
+    );
+
+    assert_eq!(
+        VM::<[u32; 6]>::restore(&snapshot).err(),
+        Some(VmError::BadRegister(6))
+    );
+    let small: Snapshot = "#state 0\n#regs 1 2 3\n".parse().unwrap();
+    assert_eq!(
+        VM::<[usize; 4]>::restore(&small).err(),
+        Some(VmError::BadRegister(3))
+    );
+}
+
+#[test]
+fn opcode_inference() {
+    let sample = |before: [usize; 4], [opcode, in1, in2, out]: [usize; 4], after| Sample {
+        before,
+        opcode: opcode as u8,
+        in1,
+        in2,
+        out,
+        after,
+    };
+
+    // The example from day 16
+    let example = sample([3, 2, 1, 1], [9, 2, 1, 2], [3, 2, 2, 1]);
+    assert_eq!(example.matching_opcodes(), vec![
+        Opcode::Addi,
+        Opcode::Mulr,
+        Opcode::Seti
+    ]);
+
+    // Numbers every opcode backwards and runs each of them on a bunch of pseudo-random registers
+    // and operands
+    let number = |opcode: Opcode| 15 - ALL_OPCODES.iter().position(|&o| o == opcode).unwrap();
+    let mut seed = 1usize;
+    let mut random = |max: usize| {
+        seed = (seed * 1_103_515_245 + 12345) % (1 << 31);
+        (seed >> 16) % max
+    };
+    let mut samples = Vec::new();
+    for &opcode in ALL_OPCODES {
+        for _ in 0..20 {
+            let before = [random(4), random(4), random(4), random(4)];
+            let [in1, in2, out] = [random(4), random(4), random(4)];
+            let mut regs: Registers<[usize; 4]> = Registers {
+                ip_register: None,
+                regs: before,
+            };
+            let instr = Instruction {
+                opcode,
+                in1,
+                in2,
+                out,
+            };
+            regs.exec(0, instr).unwrap();
+            samples.push(sample(before, [number(opcode), in1, in2, out], regs.regs));
+        }
+    }
+
+    let mappings = infer_opcodes(&samples).unwrap();
+    assert_eq!(mappings.len(), 16);
+    for &opcode in ALL_OPCODES {
+        assert_eq!(mappings[&(number(opcode) as u8)], opcode);
+    }
+
+    // With only the example, nothing can be told apart
+    let report = infer_opcodes(&[example]).unwrap_err();
+    assert_eq!(report.ambiguous[&9].len(), 3);
+
+    let impossible = sample([0, 0, 0, 0], [1, 0, 0, 0], [5, 0, 0, 0]);
+    let seti_only = sample([0, 0, 0, 0], [2, 5, 6, 1], [0, 5, 0, 0]);
+    let also_seti = sample([0, 0, 0, 0], [3, 5, 6, 1], [0, 5, 0, 0]);
+    let report = infer_opcodes(&[impossible, seti_only, also_seti]).unwrap_err();
+    assert_eq!(report.unexplained, vec![0]);
+    assert_eq!(report.conflict, Some((Opcode::Seti, [2, 3])));
 }
@@ -0,0 +1,296 @@
+//! An alternative to interpreting programs with `VM` that turns every instruction into a closure
+//! specialized for its opcode and operands ahead of time.
+//!
+//! The interpreter writes the instruction pointer into its register before every instruction and
+//! reads it back afterwards.  Here, reads of that register are replaced with the instruction's
+//! address, which is all they can ever see, and only instructions that write to it compute a new
+//! instruction pointer.  Everything else just continues with the next address.
+
+extern crate test;
+
+use crate::asm_common::*;
+
+/// A compiled instruction.  Returns the address of the next one to run.
+type Op<R> = Box<Fn(&mut Registers<R>) -> Result<usize, VmError>>;
+
+/// Where an instruction reads one of its inputs from
+#[derive(Clone, Copy)]
+enum Src<W> {
+    Reg(usize),
+    Const(W),
+}
+
+/// Builds the closure for an instruction at `ip` that computes `f(a, b)`, which returns `None` if
+/// it overflows.  `out` is `None` if the result is written to the instruction pointer's register,
+/// making this a jump.
+fn binary<R, F>(ip: usize, a: Src<R::Word>, b: Src<R::Word>, out: Option<usize>, f: F) -> Op<R>
+where
+    R: RegisterFile + 'static,
+    F: Fn(R::Word, R::Word) -> Option<R::Word> + 'static,
+{
+    let next = ip + 1;
+    // Jumps past the end of the address space halt the program like any other jump out of it
+    let jump = |target: Option<R::Word>| {
+        let target = target.ok_or(VmError::Overflow)?;
+        Ok(target
+            .to_usize()
+            .ok_or(VmError::IpOutOfRange)?
+            .saturating_add(1))
+    };
+
+    match (a, b, out) {
+        (Src::Reg(a), Src::Reg(b), Some(out)) => Box::new(move |regs| {
+            regs[out] = f(regs[a], regs[b]).ok_or(VmError::Overflow)?;
+            Ok(next)
+        }),
+        (Src::Reg(a), Src::Const(b), Some(out)) => Box::new(move |regs| {
+            regs[out] = f(regs[a], b).ok_or(VmError::Overflow)?;
+            Ok(next)
+        }),
+        (Src::Const(a), Src::Reg(b), Some(out)) => Box::new(move |regs| {
+            regs[out] = f(a, regs[b]).ok_or(VmError::Overflow)?;
+            Ok(next)
+        }),
+        (Src::Const(a), Src::Const(b), Some(out)) => {
+            let value = f(a, b);
+            Box::new(move |regs| {
+                regs[out] = value.ok_or(VmError::Overflow)?;
+                Ok(next)
+            })
+        },
+        (Src::Reg(a), Src::Reg(b), None) => Box::new(move |regs| jump(f(regs[a], regs[b]))),
+        (Src::Reg(a), Src::Const(b), None) => Box::new(move |regs| jump(f(regs[a], b))),
+        (Src::Const(a), Src::Reg(b), None) => Box::new(move |regs| jump(f(a, regs[b]))),
+        (Src::Const(a), Src::Const(b), None) => {
+            let target = jump(f(a, b));
+            Box::new(move |_| target.clone())
+        },
+    }
+}
+
+fn compile_instruction<R: RegisterFile + 'static>(
+    ip: usize,
+    instr: Instruction,
+    ip_register: Option<usize>,
+    register_count: usize,
+) -> Op<R> {
+    let src = |operand: usize, kind: Operand| match kind {
+        Operand::Register if Some(operand) == ip_register => R::Word::from_usize(ip)
+            .map(Src::Const)
+            .ok_or(VmError::IpOutOfRange),
+        Operand::Register => Ok(Src::Reg(operand)),
+        Operand::Immediate => R::Word::from_usize(operand)
+            .map(Src::Const)
+            .ok_or(VmError::Overflow),
+        Operand::Ignored => Ok(Src::Const(R::Word::default())),
+    };
+    let (kind1, kind2) = instr.opcode.operands();
+    // Checked in the same order as the interpreter uses the registers
+    let bad_register = ip_register
+        .map(|reg| (reg, Operand::Register))
+        .into_iter()
+        .chain(vec![
+            (instr.in1, kind1),
+            (instr.in2, kind2),
+            (instr.out, Operand::Register),
+        ])
+        .find(|&(reg, kind)| kind == Operand::Register && reg >= register_count)
+        .map(|(reg, _)| reg);
+    if let Some(reg) = bad_register {
+        return Box::new(move |_| Err(VmError::BadRegister(reg)));
+    }
+
+    let (a, b) = match (src(instr.in1, kind1), src(instr.in2, kind2)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(err), _) | (_, Err(err)) => return Box::new(move |_| Err(err.clone())),
+    };
+    let out = if Some(instr.out) == ip_register {
+        None
+    } else {
+        Some(instr.out)
+    };
+    let flag = |condition: bool| R::Word::from_usize(condition as usize);
+
+    match instr.opcode {
+        Opcode::Addr | Opcode::Addi => binary(ip, a, b, out, |a: R::Word, b| a.try_add(b)),
+        Opcode::Mulr | Opcode::Muli => binary(ip, a, b, out, |a: R::Word, b| a.try_mul(b)),
+        Opcode::Banr | Opcode::Bani => binary(ip, a, b, out, |a, b| Some(a & b)),
+        Opcode::Borr | Opcode::Bori => binary(ip, a, b, out, |a, b| Some(a | b)),
+        Opcode::Setr | Opcode::Seti => binary(ip, a, b, out, |a, _| Some(a)),
+        Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr =>
+            binary(ip, a, b, out, move |a, b| flag(a > b)),
+        Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr =>
+            binary(ip, a, b, out, move |a, b| flag(a == b)),
+    }
+}
+
+/// A program compiled for a VM over `R`, the registers of days 19 and 21 by default
+pub struct CompiledProgram<R: RegisterFile = [usize; REGISTER_COUNT]> {
+    ops: Vec<Op<R>>,
+}
+
+impl<R: RegisterFile + 'static> CompiledProgram<R> {
+    /// Compiles `instructions`, including any super-instructions attached to them by the
+    /// optimizer.
+    pub fn new(instructions: &Instructions, ip_register: Option<usize>) -> Self {
+        let register_count = Registers::<R>::new(None).count();
+        let ops = instructions
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(ip, &instr)| -> Op<R> {
+                match instructions.fused.get(ip) {
+                    Some(&Some(fused)) => Box::new(move |regs: &mut Registers<R>| fused.exec(regs)),
+                    _ => compile_instruction(ip, instr, ip_register, register_count),
+                }
+            })
+            .collect();
+
+        CompiledProgram { ops }
+    }
+
+    /// Runs the program from the VM's current instruction until it halts or an instruction fails,
+    /// leaving the VM in the same state as `VM::run` would.
+    pub fn run(&self, vm: &mut VM<R>) -> Result<(), VmError> {
+        let start = vm.ip();
+        let mut ip = start;
+        let mut result = Ok(());
+        while let Some(op) = self.ops.get(ip) {
+            match op(&mut vm.regs) {
+                Ok(next) => ip = next,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                },
+            }
+        }
+        vm.instructions.ip = ip;
+
+        // The interpreter always leaves the register holding one less than the final instruction
+        // pointer, either because it was written there before an instruction or because a jump
+        // wrote it.  An instruction that fails has only had its own address written.
+        let word = |ip: usize| R::Word::from_usize(ip).ok_or(VmError::IpOutOfRange);
+        if let Some(ip_register) = vm.regs.ip_register {
+            if result.is_err() {
+                vm.regs.set(ip_register, word(ip)?)?;
+            } else if ip != start {
+                vm.regs.set(ip_register, word(ip - 1)?)?;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+use crate::optimizer::optimize;
+#[cfg(test)]
+use std::num::Wrapping;
+
+/// Day 21's program halts as soon as register 0 matches the first value it checks.
+#[cfg(test)]
+const DAY21_HALTING_REG0: usize = 10_720_163;
+
+#[cfg(test)]
+fn load_vm(source: &str, reg0: usize) -> VM {
+    let mut vm = VM::new(&source.parse().unwrap());
+    vm.regs[0] = reg0;
+    vm
+}
+
+#[test]
+fn compiled_matches_interpreter() {
+    for &(source, reg0) in &[
+        (include_str!("../input/day19.txt"), 0),
+        (include_str!("../input/day21.txt"), DAY21_HALTING_REG0),
+    ] {
+        let mut expected = load_vm(source, reg0);
+        expected.run().unwrap();
+
+        let mut actual = load_vm(source, reg0);
+        CompiledProgram::new(&actual.instructions, actual.regs.ip_register)
+            .run(&mut actual)
+            .unwrap();
+
+        assert_eq!(actual.regs.regs, expected.regs.regs);
+        assert_eq!(actual.ip(), expected.ip());
+    }
+
+    let mut optimized = load_vm(include_str!("../input/day19.txt"), 1);
+    optimize(&mut optimized.instructions, optimized.regs.ip_register);
+    CompiledProgram::new(&optimized.instructions, optimized.regs.ip_register)
+        .run(&mut optimized)
+        .unwrap();
+    assert_eq!(optimized.regs[0], 18_992_484);
+}
+
+#[test]
+fn compiled_register_files() {
+    // Counts register 1 up to 3 in a VM with only 4 registers
+    let source = "#ip 0\nseti 0 0 1\naddi 1 1 1\ngtri 1 2 2\naddr 0 2 0\nseti 0 0 3\n";
+    let program = Program::parse_with_register_count(source, 4).unwrap();
+    let mut expected: VM<[u32; 4]> = VM::new(&program);
+    expected.run().unwrap();
+
+    let mut actual: VM<[u32; 4]> = VM::new(&program);
+    CompiledProgram::new(&actual.instructions, actual.regs.ip_register)
+        .run(&mut actual)
+        .unwrap();
+    assert_eq!(actual.regs.regs, expected.regs.regs);
+    assert_eq!(actual.ip(), expected.ip());
+
+    // Day 21 overflows 32 bits unless the registers wrap around
+    let program = include_str!("../input/day21.txt").parse().unwrap();
+    let mut checked: VM<[u32; 6]> = VM::new(&program);
+    checked.regs[0] = DAY21_HALTING_REG0 as u32;
+    let compiled = CompiledProgram::new(&checked.instructions, checked.regs.ip_register);
+    assert_eq!(compiled.run(&mut checked), Err(VmError::Overflow));
+
+    let mut wrapping: VM<[Wrapping<u32>; 6]> = VM::new(&program);
+    wrapping.regs[0] = Wrapping(DAY21_HALTING_REG0 as u32);
+    let compiled = CompiledProgram::new(&wrapping.instructions, wrapping.regs.ip_register);
+    assert_eq!(compiled.run(&mut wrapping), Ok(()));
+
+    let mut small: VM<[usize; 4]> = VM::new(&"seti 5 0 1\naddr 1 4 2\n".parse().unwrap());
+    let compiled = CompiledProgram::new(&small.instructions, small.regs.ip_register);
+    assert_eq!(compiled.run(&mut small), Err(VmError::BadRegister(4)));
+    assert_eq!(small.ip(), 1);
+}
+
+#[cfg(test)]
+fn bench_vm(b: &mut test::Bencher, source: &str, reg0: usize) {
+    let vm = load_vm(source, reg0);
+    b.iter(|| {
+        let mut vm = vm.clone();
+        vm.run().unwrap();
+        vm.regs[0]
+    })
+}
+
+#[cfg(test)]
+fn bench_compiled(b: &mut test::Bencher, source: &str, reg0: usize) {
+    let vm = load_vm(source, reg0);
+    let compiled = CompiledProgram::new(&vm.instructions, vm.regs.ip_register);
+    b.iter(|| {
+        let mut vm = vm.clone();
+        compiled.run(&mut vm).unwrap();
+        vm.regs[0]
+    })
+}
+
+#[bench]
+fn bench_day19_vm(b: &mut test::Bencher) { bench_vm(b, include_str!("../input/day19.txt"), 0) }
+
+#[bench]
+fn bench_day19_compiled(b: &mut test::Bencher) {
+    bench_compiled(b, include_str!("../input/day19.txt"), 0)
+}
+
+#[bench]
+fn bench_day21_vm(b: &mut test::Bencher) {
+    bench_vm(b, include_str!("../input/day21.txt"), DAY21_HALTING_REG0)
+}
+
+#[bench]
+fn bench_day21_compiled(b: &mut test::Bencher) {
+    bench_compiled(b, include_str!("../input/day21.txt"), DAY21_HALTING_REG0)
+}
@@ -1,23 +1,26 @@
 use std::collections::HashSet;
 
+use crate::solution::Solution;
+
 const INPUT: &str = include_str!("../input/day1.txt");
 
-fn parse_input() -> impl Iterator<Item = isize> + Clone {
-    INPUT
+fn parse_input(input: &str) -> Vec<isize> {
+    input
         .split('\n')
         .map(|n| n.parse().map_err(|_| ()))
         .filter(Result::is_ok)
         .map(Result::unwrap)
+        .collect()
 }
 
-fn part1() -> isize { parse_input().sum() }
+fn part1(input: &[isize]) -> isize { input.iter().sum() }
 
-fn part2() -> isize {
+fn part2(input: &[isize]) -> isize {
     let mut seen_frequencies: HashSet<isize> = HashSet::new();
     seen_frequencies.insert(0);
 
     let mut cur_num = 0;
-    for num in parse_input().cycle() {
+    for num in input.iter().cycle() {
         cur_num += num;
         if !seen_frequencies.insert(cur_num) {
             return cur_num;
@@ -27,7 +30,18 @@ fn part2() -> isize {
     unreachable!();
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day1;
+
+impl Solution for Day1 {
+    type Input = Vec<isize>;
+    type Part1 = isize;
+    type Part2 = isize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<isize> { parse_input(input) }
+
+    fn part1(input: &Vec<isize>) -> isize { part1(input) }
+
+    fn part2(input: &Vec<isize>) -> isize { part2(input) }
 }
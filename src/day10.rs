@@ -2,6 +2,8 @@ use std::isize;
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex =
         Regex::new("position=< *(-?\\d+), +(-?\\d+)> velocity=< *(-?\\d+), +(-?\\d+)>").unwrap();
@@ -9,24 +11,27 @@ lazy_static! {
 
 const INPUT: &str = include_str!("../input/day10.txt");
 
-struct Line {
+#[derive(Clone)]
+pub struct Line {
     pub pos_x: isize,
     pub pos_y: isize,
     pub velocity_x: isize,
     pub velocity_y: isize,
 }
 
-fn parse_input() -> impl Iterator<Item = Line> {
-    RGX.captures_iter(INPUT).map(|cap| Line {
-        pos_x: cap[1].parse().unwrap(),
-        pos_y: cap[2].parse().unwrap(),
-        velocity_x: cap[3].parse().unwrap(),
-        velocity_y: cap[4].parse().unwrap(),
-    })
+fn parse_input(input: &str) -> Vec<Line> {
+    RGX.captures_iter(input)
+        .map(|cap| Line {
+            pos_x: cap[1].parse().unwrap(),
+            pos_y: cap[2].parse().unwrap(),
+            velocity_x: cap[3].parse().unwrap(),
+            velocity_y: cap[4].parse().unwrap(),
+        })
+        .collect()
 }
 
-fn solve() -> (String, usize) {
-    let mut points = parse_input().collect::<Vec<_>>();
+fn solve(lines: &[Line]) -> (String, usize) {
+    let mut points = lines.to_vec();
 
     let mut min_y_range = isize::max_value();
     let mut i = 0;
@@ -54,10 +59,22 @@ fn solve() -> (String, usize) {
                     [(line.pos_x - line.velocity_x - min_x) as usize] = '#';
             }
 
-            let message = grid
+            // The grid has some empty padding around the message, which is trimmed off of every
+            // side.  The left edge is the indent shared by all of the message's rows.
+            let rows: Vec<String> = grid
                 .into_iter()
-                .map(|line| line.into_iter().collect::<String>())
-                .collect::<Vec<String>>()
+                .map(|line| line.into_iter().collect::<String>().trim_end().to_owned())
+                .filter(|line| !line.is_empty())
+                .collect();
+            let indent = rows
+                .iter()
+                .map(|row| row.len() - row.trim_start().len())
+                .min()
+                .unwrap_or(0);
+            let message = rows
+                .iter()
+                .map(|row| &row[indent..])
+                .collect::<Vec<&str>>()
                 .join("\n");
             return (message, i);
         }
@@ -67,8 +84,22 @@ fn solve() -> (String, usize) {
     }
 }
 
-pub fn run() {
-    let (part1, part2) = solve();
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+fn part1(lines: &[Line]) -> String { solve(lines).0 }
+
+fn part2(lines: &[Line]) -> usize { solve(lines).1 }
+
+pub struct Day10;
+
+impl Solution for Day10 {
+    type Input = Vec<Line>;
+    type Part1 = String;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Line> { parse_input(input) }
+
+    fn part1(lines: &Vec<Line>) -> String { part1(lines) }
+
+    fn part2(lines: &Vec<Line>) -> usize { part2(lines) }
 }
@@ -1,16 +1,16 @@
-const INPUT: &str = include_str!("../input/day11.txt");
+use crate::solution::Solution;
 
-fn parse_input() -> usize { INPUT.trim().parse().unwrap() }
+const INPUT: &str = include_str!("../input/day11.txt");
 
-fn populate_powers() -> [[i32; 300]; 300] {
-    let input = parse_input();
+fn parse_input(input: &str) -> usize { input.trim().parse().unwrap() }
 
+fn populate_powers(serial_number: usize) -> [[i32; 300]; 300] {
     let mut powers = [[0; 300]; 300];
     for y in 0..300 {
         for x in 0..300 {
             let rack_id = x + 10;
             let mut power = rack_id * y;
-            power += input;
+            power += serial_number;
             power *= rack_id;
             let power_s = power.to_string();
             let hundreds: u32 = match power_s.chars().nth(power_s.len() - 3) {
@@ -24,8 +24,8 @@ fn populate_powers() -> [[i32; 300]; 300] {
     powers
 }
 
-fn part1() -> (usize, usize) {
-    let powers = populate_powers();
+fn part1(serial_number: usize) -> String {
+    let powers = populate_powers(serial_number);
     let (mut best_x, mut best_y, mut best) = (0, 0, 0);
     for y in 0..(300 - 3) {
         for x in 0..(300 - 3) {
@@ -44,11 +44,11 @@ fn part1() -> (usize, usize) {
         }
     }
 
-    (best_x, best_y)
+    format!("{},{}", best_x, best_y)
 }
 
-fn part2() -> (usize, usize, usize) {
-    let powers: [[i32; 300]; 300] = populate_powers();
+fn part2(serial_number: usize) -> String {
+    let powers: [[i32; 300]; 300] = populate_powers(serial_number);
 
     let (mut best_x, mut best_y, mut best, mut best_size) = (0, 0, 0, 0);
     let mut sums = [[0; 300]; 300];
@@ -73,10 +73,21 @@ fn part2() -> (usize, usize, usize) {
         }
     }
 
-    (best_x, best_y, best_size)
+    format!("{},{},{}", best_x, best_y, best_size)
 }
 
-pub fn run() {
-    println!("Part 1: {:?}", part1());
-    println!("Part 2: {:?}", part2());
+pub struct Day11;
+
+impl Solution for Day11 {
+    type Input = usize;
+    type Part1 = String;
+    type Part2 = String;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> usize { parse_input(input) }
+
+    fn part1(&serial_number: &usize) -> String { part1(serial_number) }
+
+    fn part2(&serial_number: &usize) -> String { part2(serial_number) }
 }
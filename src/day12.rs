@@ -2,14 +2,18 @@ use std::iter;
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex = Regex::new("initial state: ([#\\.]+)").unwrap();
 }
 
 const INPUT: &str = include_str!("../input/day12.txt");
 
-fn parse_input() -> (Vec<bool>, Vec<([bool; 5], bool)>) {
-    let mut lines = INPUT.lines();
+type Rule = ([bool; 5], bool);
+
+fn parse_input(input: &str) -> (Vec<bool>, Vec<Rule>) {
+    let mut lines = input.lines();
     let initial_state = &RGX.captures(lines.next().unwrap()).unwrap()[1];
     let parsed_initial_state = initial_state.chars().map(|c| c == '#');
     let padding = iter::repeat(false).take(PADDING_SIZE);
@@ -36,8 +40,8 @@ fn parse_input() -> (Vec<bool>, Vec<([bool; 5], bool)>) {
 
 const PADDING_SIZE: usize = 250;
 
-fn solve() -> (isize, i64) {
-    let (mut state, rules) = parse_input();
+fn solve(initial_state: &[bool], rules: &[Rule]) -> (isize, i64) {
+    let mut state = initial_state.to_vec();
 
     let mut critical_section = Vec::new();
     let mut critical_section_start = 0;
@@ -57,7 +61,7 @@ fn solve() -> (isize, i64) {
                 critical_section_end = i;
             }
 
-            for (rule, is_pot) in &rules {
+            for (rule, is_pot) in rules {
                 if state[(i - 2)..=(i + 2)] == *rule {
                     new_state[i] = *is_pot;
                 }
@@ -112,8 +116,22 @@ fn solve() -> (isize, i64) {
     (total_for_20, total_for_50_billion)
 }
 
-pub fn run() {
-    let (part1, part2) = solve();
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+fn part1((initial_state, rules): &(Vec<bool>, Vec<Rule>)) -> isize { solve(initial_state, rules).0 }
+
+fn part2((initial_state, rules): &(Vec<bool>, Vec<Rule>)) -> i64 { solve(initial_state, rules).1 }
+
+pub struct Day12;
+
+impl Solution for Day12 {
+    type Input = (Vec<bool>, Vec<Rule>);
+    type Part1 = isize;
+    type Part2 = i64;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> (Vec<bool>, Vec<Rule>) { parse_input(input) }
+
+    fn part1(pots: &(Vec<bool>, Vec<Rule>)) -> isize { part1(pots) }
+
+    fn part2(pots: &(Vec<bool>, Vec<Rule>)) -> i64 { part2(pots) }
 }
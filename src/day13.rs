@@ -1,8 +1,11 @@
 use std::collections::{HashMap, HashSet};
+
+use crate::solution::Solution;
+
 const INPUT: &str = include_str!("../input/day13.txt");
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-enum Track {
+pub enum Track {
     Vertical,
     Horizontal,
     Intersection,
@@ -11,7 +14,7 @@ enum Track {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-enum Direction {
+pub enum Direction {
     Up,
     Down,
     Left,
@@ -39,7 +42,7 @@ impl Direction {
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
-struct Cart {
+pub struct Cart {
     pub direction: Direction,
     pub x: usize,
     pub y: usize,
@@ -102,9 +105,11 @@ impl Cart {
     }
 }
 
-fn parse_input() -> (Vec<Vec<Option<Track>>>, Vec<Cart>) {
+type Tracks = Vec<Vec<Option<Track>>>;
+
+fn parse_input(input: &str) -> (Tracks, Vec<Cart>) {
     let mut carts = Vec::new();
-    let tracks = INPUT
+    let tracks = input
         .lines()
         .filter(|l| l.len() > 1)
         .enumerate()
@@ -194,8 +199,8 @@ fn find_collision_location(mut iter: impl Iterator<Item = Cart>) -> Option<(usiz
         .map(|cart| (cart.x, cart.y))
 }
 
-fn part1() -> (usize, usize) {
-    let (tracks, mut carts) = parse_input();
+fn part1((tracks, carts): &(Tracks, Vec<Cart>)) -> String {
+    let mut carts = carts.clone();
 
     loop {
         carts.sort_unstable_by(|c1, c2| (c1.y, c1.x).cmp(&(c2.y, c2.x)));
@@ -203,8 +208,8 @@ fn part1() -> (usize, usize) {
         for (i, cart) in carts.iter_mut().enumerate() {
             cart.next_movement(tracks[cart.y][cart.x].unwrap());
             new_carts[i] = cart.clone();
-            if let Some(collision) = find_collision_location(new_carts.iter().cloned()) {
-                return collision;
+            if let Some((x, y)) = find_collision_location(new_carts.iter().cloned()) {
+                return format!("{},{}", x, y);
             }
         }
         carts = new_carts;
@@ -233,8 +238,8 @@ fn find_collision_location_and_indices(
         .map(|(i, cart)| ((cart.x, cart.y), i, uniq[&(cart.x, cart.y)]))
 }
 
-fn part2() -> (usize, usize) {
-    let (tracks, mut carts) = parse_input();
+fn part2((tracks, carts): &(Tracks, Vec<Cart>)) -> String {
+    let mut carts = carts.clone();
 
     loop {
         carts.sort_unstable_by(|c1, c2| (c1.y, c1.x).cmp(&(c2.y, c2.x)));
@@ -258,12 +263,23 @@ fn part2() -> (usize, usize) {
             .collect();
         if carts.len() == 1 {
             let last_cart = &carts[0];
-            return (last_cart.x, last_cart.y);
+            return format!("{},{}", last_cart.x, last_cart.y);
         }
     }
 }
 
-pub fn run() {
-    println!("Part 1: {:?}", part1());
-    println!("Part 2: {:?}", part2());
+pub struct Day13;
+
+impl Solution for Day13 {
+    type Input = (Tracks, Vec<Cart>);
+    type Part1 = String;
+    type Part2 = String;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> (Tracks, Vec<Cart>) { parse_input(input) }
+
+    fn part1(track_layout: &(Tracks, Vec<Cart>)) -> String { part1(track_layout) }
+
+    fn part2(track_layout: &(Tracks, Vec<Cart>)) -> String { part2(track_layout) }
 }
@@ -1,9 +1,12 @@
+use crate::solution::Solution;
+
 const INPUT: &str = include_str!("../input/day14.txt");
 
-fn parse_input() -> usize { INPUT.trim().parse().unwrap() }
+// Kept as a string since leading zeroes are significant for part 2
+fn parse_input(input: &str) -> String { input.trim().to_owned() }
 
-fn part1() -> u64 {
-    let input = parse_input();
+fn part1(input: &str) -> u64 {
+    let input: usize = input.parse().unwrap();
     let mut recipes = vec![3, 7];
 
     let mut elf1_index = 0;
@@ -26,12 +29,8 @@ fn part1() -> u64 {
         })
 }
 
-fn part2() -> usize {
-    let input: Vec<u32> = parse_input()
-        .to_string()
-        .chars()
-        .map(|c| c.to_digit(10).unwrap())
-        .collect();
+fn part2(input: &str) -> usize {
+    let input: Vec<u32> = input.chars().map(|c| c.to_digit(10).unwrap()).collect();
     let mut recipes = vec![3, 7];
 
     let mut elf1_index = 0;
@@ -61,7 +60,18 @@ fn part2() -> usize {
     res.unwrap()
 }
 
-pub fn run() {
-    println!("Part 1: {:?}", part1());
-    println!("Part 2: {:?}", part2());
+pub struct Day14;
+
+impl Solution for Day14 {
+    type Input = String;
+    type Part1 = u64;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> String { parse_input(input) }
+
+    fn part1(input: &String) -> u64 { part1(input) }
+
+    fn part2(input: &String) -> usize { part2(input) }
 }
@@ -0,0 +1,1161 @@
+extern crate test;
+
+#[cfg(test)]
+use pathfinding::prelude::*;
+
+use crate::solution::Solution;
+
+pub mod replay;
+
+use self::replay::{Frame, Replay};
+
+const INPUT: &str = include_str!("../../input/day15.txt");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cell {
+    Elf(usize),
+    Goblin(usize),
+    Blank,
+    Wall,
+}
+
+impl std::fmt::Display for Cell {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let c = match self {
+            Cell::Elf(_) => 'E',
+            Cell::Goblin(_) => 'G',
+            Cell::Blank => '.',
+            Cell::Wall => '#',
+        };
+        write!(fmt, "{}", c)
+    }
+}
+
+impl Cell {
+    /// Parses a map square, giving units the default hit points
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '#' => Some(Cell::Wall),
+            '.' => Some(Cell::Blank),
+            'E' => Some(Cell::Elf(Stats::default().hit_points)),
+            'G' => Some(Cell::Goblin(Stats::default().hit_points)),
+            _ => None,
+        }
+    }
+
+    pub fn is_enemy(self, other: Self) -> bool {
+        match (self, other) {
+            (Cell::Elf(_), Cell::Goblin(_)) | (Cell::Goblin(_), Cell::Elf(_)) => true,
+            _ => false,
+        }
+    }
+
+    pub fn hit_points(self) -> Option<usize> {
+        match self {
+            Cell::Elf(hp) | Cell::Goblin(hp) => Some(hp),
+            _ => None,
+        }
+    }
+
+    pub fn is_traversable(self) -> bool { self == Cell::Blank }
+}
+
+/// A square of the battlefield as a `Battle` keeps it, where units are looked up in the unit
+/// table by id
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Square {
+    Wall,
+    Blank,
+    Unit(usize),
+}
+
+/// The attack power and starting hit points of every unit in a faction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub attack_power: usize,
+    pub hit_points: usize,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            attack_power: 3,
+            hit_points: 200,
+        }
+    }
+}
+
+/// Parses a battle map, which must be rectangular.  Empty lines are skipped.
+pub fn parse_map(input: &str) -> Result<Vec<Vec<Cell>>, String> {
+    let mut map: Vec<Vec<Cell>> = Vec::new();
+    for (line_ix, line) in input.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+
+        let row = line
+            .chars()
+            .enumerate()
+            .map(|(column, c)| {
+                Cell::from_char(c).ok_or_else(|| {
+                    format!("{}:{}: Invalid square `{}`", line_ix + 1, column + 1, c)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(first) = map.first() {
+            if row.len() != first.len() {
+                return Err(format!(
+                    "{}: Expected {} squares like the rows before, found {}",
+                    line_ix + 1,
+                    first.len(),
+                    row.len()
+                ));
+            }
+        }
+        map.push(row);
+    }
+
+    Ok(map)
+}
+
+fn parse_input(input: &str) -> Vec<Vec<Cell>> {
+    parse_map(input).unwrap_or_else(|err| panic!("Invalid map: {}", err))
+}
+
+fn iter_neighbors<'a, T: Copy, P: Fn(T) -> bool + 'a>(
+    state: &'a [Vec<T>],
+    x: usize,
+    y: usize,
+    pred: P,
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .map(move |(x_diff, y_diff)| (x as isize + x_diff, y as isize + y_diff))
+        .filter(move |&(xa, ya)| {
+            xa >= 0
+                && ya >= 0
+                && (ya as usize) < state.len()
+                && (xa as usize) < state[ya as usize].len()
+                && pred(state[ya as usize][xa as usize])
+        })
+        .map(move |(xa, ya)| (xa as usize, ya as usize))
+}
+
+fn iter_blank_neighbors<'a>(
+    grid: &'a [Vec<Square>],
+    x: usize,
+    y: usize,
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    iter_neighbors(grid, x, y, |square| square == Square::Blank)
+}
+
+/// Whether `square` holds a living unit that isn't part of `faction`
+fn is_enemy(units: &[Unit], faction: Faction, square: Square) -> bool {
+    match square {
+        Square::Unit(id) => units[id].faction != faction,
+        _ => false,
+    }
+}
+
+/// Floods outwards from the unit at `(x, y)` through open squares and returns the step it should
+/// take towards the nearest square in range of an enemy.  Ties between equally near squares and
+/// between first steps along equally short paths are both broken by reading order.
+fn find_step(grid: &[Vec<Square>], units: &[Unit], x: usize, y: usize) -> Option<(usize, usize)> {
+    let faction = match grid[y][x] {
+        Square::Unit(id) => units[id].faction,
+        square => panic!("There's no unit at {},{}: {:?}", x, y, square),
+    };
+    let width = grid[0].len();
+    let in_range = |&(xa, ya): &(usize, usize)| {
+        iter_neighbors(grid, xa, ya, |square| is_enemy(units, faction, square))
+            .next()
+            .is_some()
+    };
+
+    // The distance to each square reached so far, along with the first step (in `(y, x)` order so
+    // that comparisons follow reading order) of the best shortest path found to it
+    let mut first_steps: Vec<Option<(usize, (usize, usize))>> = vec![None; width * grid.len()];
+    first_steps[y * width + x] = Some((0, (y, x)));
+
+    let mut frontier: Vec<(usize, usize)> = iter_blank_neighbors(grid, x, y).collect();
+    for &(xa, ya) in &frontier {
+        first_steps[ya * width + xa] = Some((1, (ya, xa)));
+    }
+
+    let mut distance = 1;
+    while !frontier.is_empty() {
+        let nearest = frontier
+            .iter()
+            .cloned()
+            .filter(in_range)
+            .min_by_key(|&(xa, ya)| (ya, xa));
+        if let Some((xa, ya)) = nearest {
+            return first_steps[ya * width + xa].map(|(_, (step_y, step_x))| (step_x, step_y));
+        }
+
+        distance += 1;
+        let mut next = Vec::new();
+        for &(xa, ya) in &frontier {
+            let (_, step) = first_steps[ya * width + xa].unwrap();
+            for (xn, yn) in iter_blank_neighbors(grid, xa, ya) {
+                let seen = &mut first_steps[yn * width + xn];
+                match *seen {
+                    None => {
+                        *seen = Some((distance, step));
+                        next.push((xn, yn));
+                    },
+                    // Only squares first reached in this pass can still get a better first step
+                    Some((seen_distance, ref mut seen_step)) =>
+                        if seen_distance == distance && step < *seen_step {
+                            *seen_step = step;
+                        },
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    None
+}
+
+/// One unit striking another, with both positions given as `(x, y)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attack {
+    pub attacker: (usize, usize),
+    pub target: (usize, usize),
+    pub damage: usize,
+    pub killed: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Elf,
+    Goblin,
+}
+
+impl Faction {
+    pub fn enemy(self) -> Self {
+        match self {
+            Faction::Elf => Faction::Goblin,
+            Faction::Goblin => Faction::Elf,
+        }
+    }
+}
+
+impl std::fmt::Display for Faction {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Faction::Elf => write!(fmt, "elf"),
+            Faction::Goblin => write!(fmt, "goblin"),
+        }
+    }
+}
+
+/// A single elf or goblin.  Its `id` is its index in the battle's unit table, which it keeps
+/// even after it has died.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unit {
+    pub id: usize,
+    pub faction: Faction,
+    /// The unit's position as `(x, y)`
+    pub position: (usize, usize),
+    pub hit_points: usize,
+    pub attack_power: usize,
+}
+
+impl Unit {
+    pub fn is_alive(&self) -> bool { self.hit_points > 0 }
+
+    /// The square that the unit occupies, holding its hit points
+    pub fn cell(&self) -> Cell {
+        match self.faction {
+            Faction::Elf => Cell::Elf(self.hit_points),
+            Faction::Goblin => Cell::Goblin(self.hit_points),
+        }
+    }
+}
+
+/// A unit being killed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Kill {
+    /// The round that the kill happened in, counting from 1
+    pub round: usize,
+    pub killer: usize,
+    pub victim: usize,
+    pub victim_faction: Faction,
+    /// Where the victim died, as `(x, y)`
+    pub position: (usize, usize),
+}
+
+/// Everything about how a battle ended that's needed to check its score
+#[derive(Clone, Debug, PartialEq)]
+pub struct BattleOutcome {
+    /// The faction that wiped out the other, or `None` if the battle was aborted before then
+    pub winner: Option<Faction>,
+    /// The number of full rounds completed
+    pub rounds: usize,
+    /// The units left alive, in order of id
+    pub survivors: Vec<Unit>,
+    pub elf_casualties: usize,
+    /// Every kill in the order that they happened
+    pub kills: Vec<Kill>,
+}
+
+impl BattleOutcome {
+    pub fn hitpoint_sum(&self) -> usize { self.survivors.iter().map(|unit| unit.hit_points).sum() }
+
+    /// The number of full rounds completed multiplied by the hit points left over, which is the
+    /// puzzle's answer
+    pub fn score(&self) -> usize { self.rounds * self.hitpoint_sum() }
+}
+
+impl std::fmt::Display for BattleOutcome {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.winner {
+            Some(Faction::Elf) => write!(fmt, "Elves win")?,
+            Some(Faction::Goblin) => write!(fmt, "Goblins win")?,
+            None => write!(fmt, "Aborted")?,
+        }
+        writeln!(
+            fmt,
+            " after {} full rounds with {} hit points left; score {}",
+            self.rounds,
+            self.hitpoint_sum(),
+            self.score()
+        )?;
+        writeln!(fmt, "Elf casualties: {}", self.elf_casualties)?;
+
+        writeln!(fmt, "Survivors:")?;
+        for unit in &self.survivors {
+            writeln!(
+                fmt,
+                "  {} #{} at {},{} with {} hit points",
+                unit.faction, unit.id, unit.position.0, unit.position.1, unit.hit_points
+            )?;
+        }
+
+        writeln!(fmt, "Kills:")?;
+        for kill in &self.kills {
+            writeln!(
+                fmt,
+                "  round {}: {} #{} killed {} #{} at {},{}",
+                kill.round,
+                kill.victim_faction.enemy(),
+                kill.killer,
+                kill.victim_faction,
+                kill.victim,
+                kill.position.0,
+                kill.position.1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A battle between elves and goblins on a rectangular map, advanced one round at a time
+#[derive(Clone, Debug)]
+pub struct Battle {
+    grid: Vec<Vec<Square>>,
+    units: Vec<Unit>,
+    elves: Stats,
+    goblins: Stats,
+    rounds: usize,
+    finished: bool,
+    elf_deaths: usize,
+    kills: Vec<Kill>,
+    abort_on_elf_death: bool,
+    replay: Option<Replay>,
+}
+
+impl Battle {
+    /// Sets up a battle on `map`, giving every unit the stats of its faction.
+    pub fn new(map: &[Vec<Cell>], elves: Stats, goblins: Stats) -> Self {
+        let width = map.first().map(Vec::len).unwrap_or(0);
+        assert!(
+            map.iter().all(|row| row.len() == width),
+            "Battle map must be rectangular"
+        );
+
+        let mut units = Vec::new();
+        let mut grid = Vec::with_capacity(map.len());
+        for (y, row) in map.iter().enumerate() {
+            let mut grid_row = Vec::with_capacity(width);
+            for (x, &cell) in row.iter().enumerate() {
+                let (faction, stats) = match cell {
+                    Cell::Elf(_) => (Faction::Elf, elves),
+                    Cell::Goblin(_) => (Faction::Goblin, goblins),
+                    Cell::Blank => {
+                        grid_row.push(Square::Blank);
+                        continue;
+                    },
+                    Cell::Wall => {
+                        grid_row.push(Square::Wall);
+                        continue;
+                    },
+                };
+                let id = units.len();
+                units.push(Unit {
+                    id,
+                    faction,
+                    position: (x, y),
+                    hit_points: stats.hit_points,
+                    attack_power: stats.attack_power,
+                });
+                grid_row.push(Square::Unit(id));
+            }
+            grid.push(grid_row);
+        }
+
+        Battle {
+            grid,
+            units,
+            elves,
+            goblins,
+            rounds: 0,
+            finished: false,
+            elf_deaths: 0,
+            kills: Vec::new(),
+            abort_on_elf_death: false,
+            replay: None,
+        }
+    }
+
+    /// The battlefield, with the squares of units holding their hit points
+    pub fn state(&self) -> Vec<Vec<Cell>> {
+        self.grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&square| match square {
+                        Square::Wall => Cell::Wall,
+                        Square::Blank => Cell::Blank,
+                        Square::Unit(id) => self.units[id].cell(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Every unit that has taken part in the battle, indexed by id and including the dead
+    pub fn units(&self) -> &[Unit] { &self.units }
+
+    /// The number of full rounds that have been completed
+    pub fn rounds(&self) -> usize { self.rounds }
+
+    /// Whether a unit has found that there are no enemies left to fight, a round went by without
+    /// anyone moving or attacking, or the battle was aborted after an elf died
+    pub fn is_finished(&self) -> bool { self.finished }
+
+    pub fn elf_deaths(&self) -> usize { self.elf_deaths }
+
+    /// Every kill so far in the order that they happened
+    pub fn kills(&self) -> &[Kill] { &self.kills }
+
+    /// Ends the battle at the start of the next unit's turn once any elf has died, for when only
+    /// flawless elf victories are of interest.
+    pub fn abort_on_elf_death(&mut self) { self.abort_on_elf_death = true; }
+
+    /// Starts recording the state of the battlefield and every attack made after each round.
+    pub fn enable_recording(&mut self) {
+        let mut replay = Replay::new(self.elves, self.goblins);
+        replay.frames.push(Frame {
+            round: self.rounds,
+            state: self.state(),
+            attacks: Vec::new(),
+        });
+        self.replay = Some(replay);
+    }
+
+    pub fn replay(&self) -> Option<&Replay> { self.replay.as_ref() }
+
+    fn living(&self) -> impl Iterator<Item = &Unit> { self.units.iter().filter(|u| u.is_alive()) }
+
+    pub fn elf_count(&self) -> usize {
+        self.living()
+            .filter(|unit| unit.faction == Faction::Elf)
+            .count()
+    }
+
+    pub fn hitpoint_sum(&self) -> usize { self.living().map(|unit| unit.hit_points).sum() }
+
+    /// Reports on the battle as it stands, which is only final once it has finished.
+    pub fn outcome(&self) -> BattleOutcome {
+        let survivors: Vec<Unit> = self.living().cloned().collect();
+        let winner = match survivors.first() {
+            Some(unit) if self.finished && survivors.iter().all(|u| u.faction == unit.faction) =>
+                Some(unit.faction),
+            _ => None,
+        };
+
+        BattleOutcome {
+            winner,
+            rounds: self.rounds,
+            survivors,
+            elf_casualties: self.elf_deaths,
+            kills: self.kills.clone(),
+        }
+    }
+
+    /// Plays out a single round, giving every unit a turn in reading order.  Returns `false`
+    /// without counting the round if combat ended partway through it, or if nobody could move or
+    /// attack, since every round after that would play out the same way.
+    pub fn step_round(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        let mut attacks = Vec::new();
+        let completed = self.play_round(&mut attacks);
+        if completed {
+            self.rounds += 1;
+        }
+
+        if self.replay.is_some() {
+            let state = self.state();
+            self.replay.as_mut().unwrap().frames.push(Frame {
+                round: self.rounds,
+                state,
+                attacks,
+            });
+        }
+        completed
+    }
+
+    /// Gives every unit a turn, returning `false` as soon as the battle ends.
+    fn play_round(&mut self, attacks: &mut Vec<Attack>) -> bool {
+        let mut moved = false;
+        let mut turn_order: Vec<usize> = self.living().map(|unit| unit.id).collect();
+        turn_order.sort_by_key(|&id| {
+            let (x, y) = self.units[id].position;
+            (y, x)
+        });
+
+        for id in turn_order {
+            if self.abort_on_elf_death && self.elf_deaths > 0 {
+                self.finished = true;
+                return false;
+            }
+            // units can be killed before their turn comes up
+            if !self.units[id].is_alive() {
+                continue;
+            }
+
+            // check if there is an enemy adjascent
+            if let Some(hit) = self.attack(id) {
+                attacks.push(hit);
+                continue;
+            }
+
+            let faction = self.units[id].faction;
+            if !self.living().any(|unit| unit.faction != faction) {
+                // no more targets to kill; one side has won
+                self.finished = true;
+                return false;
+            }
+
+            let (x, y) = self.units[id].position;
+            if let Some((dst_x, dst_y)) = find_step(&self.grid, &self.units, x, y) {
+                // move along the path towards the destination
+                debug_assert_eq!(self.grid[dst_y][dst_x], Square::Blank);
+                self.grid[dst_y][dst_x] = Square::Unit(id);
+                self.grid[y][x] = Square::Blank;
+                self.units[id].position = (dst_x, dst_y);
+                moved = true;
+
+                // check if there is an enemy adjascent and attack if there is
+                if let Some(hit) = self.attack(id) {
+                    attacks.push(hit);
+                }
+            }
+        }
+
+        if !moved && attacks.is_empty() {
+            // a stalemate, such as when the factions are walled off from each other
+            self.finished = true;
+            return false;
+        }
+        true
+    }
+
+    /// Has unit `id` attack the weakest enemy next to it, breaking ties in reading order.
+    fn attack(&mut self, id: usize) -> Option<Attack> {
+        let (x, y) = self.units[id].position;
+        let faction = self.units[id].faction;
+        let target_id = iter_neighbors(&self.grid, x, y, |square| {
+            is_enemy(&self.units, faction, square)
+        })
+        .map(|(xa, ya)| match self.grid[ya][xa] {
+            Square::Unit(target_id) => target_id,
+            _ => unreachable!(),
+        })
+        .min_by_key(|&target_id| {
+            let target = &self.units[target_id];
+            (target.hit_points, target.position.1, target.position.0)
+        })?;
+
+        let damage = self.units[id].attack_power;
+        let target = &mut self.units[target_id];
+        target.hit_points = target.hit_points.saturating_sub(damage);
+        let killed = !target.is_alive();
+        let (target_x, target_y) = target.position;
+        if killed {
+            self.grid[target_y][target_x] = Square::Blank;
+            if target.faction == Faction::Elf {
+                self.elf_deaths += 1;
+            }
+            self.kills.push(Kill {
+                round: self.rounds + 1,
+                killer: id,
+                victim: target_id,
+                victim_faction: target.faction,
+                position: (target_x, target_y),
+            });
+        }
+
+        Some(Attack {
+            attacker: (x, y),
+            target: (target_x, target_y),
+            damage,
+            killed,
+        })
+    }
+}
+
+/// Writes out a battlefield, with the hit points of the units in each row listed after it
+fn write_state(fmt: &mut std::fmt::Formatter, state: &[Vec<Cell>]) -> std::fmt::Result {
+    for row in state {
+        for cell in row {
+            write!(fmt, "{}", cell)?;
+        }
+
+        let units: Vec<String> = row
+            .iter()
+            .filter_map(|&cell| cell.hit_points().map(|hp| format!("{}({})", cell, hp)))
+            .collect();
+        if !units.is_empty() {
+            write!(fmt, "   {}", units.join(", "))?;
+        }
+        writeln!(fmt)?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for Battle {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write_state(fmt, &self.state())
+    }
+}
+
+fn solve(
+    initial_state: &[Vec<Cell>],
+    cur_elf_attack_power: usize,
+    elves_must_win: bool,
+) -> Option<BattleOutcome> {
+    let elves = Stats {
+        attack_power: cur_elf_attack_power,
+        ..Stats::default()
+    };
+    let mut battle = Battle::new(initial_state, elves, Stats::default());
+    if elves_must_win {
+        battle.abort_on_elf_death();
+    }
+
+    while battle.step_round() {}
+
+    if elves_must_win && battle.elf_deaths() > 0 {
+        return None;
+    }
+
+    Some(battle.outcome())
+}
+
+/// A battle's score, which is the answer to both parts, along with how it came about
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scored<T> {
+    pub score: usize,
+    pub details: T,
+}
+
+impl<T> std::fmt::Display for Scored<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.score)
+    }
+}
+
+fn part1(initial_state: &[Vec<Cell>]) -> Scored<BattleOutcome> {
+    let outcome = solve(initial_state, 3, false).unwrap();
+    Scored {
+        score: outcome.score(),
+        details: outcome,
+    }
+}
+
+/// The result of searching for the weakest elves that win without losing anyone
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerSearch {
+    pub attack_power: usize,
+    pub outcome: BattleOutcome,
+    pub simulations: usize,
+}
+
+/// Finds the lowest elf attack power at which no elf dies, assuming that more attack power never
+/// gets an elf killed.  The power is doubled until the elves win and then binary searched
+/// between the last loss and the first win.
+fn search_attack_power(initial_state: &[Vec<Cell>]) -> PowerSearch {
+    let mut simulations = 0;
+    let mut simulate = |attack_power| {
+        simulations += 1;
+        solve(initial_state, attack_power, true)
+    };
+
+    let mut lost = Stats::default().attack_power - 1;
+    let mut won = Stats::default().attack_power;
+    let mut outcome = loop {
+        match simulate(won) {
+            Some(outcome) => break outcome,
+            None => {
+                lost = won;
+                won *= 2;
+            },
+        }
+    };
+
+    while won - lost > 1 {
+        let mid = lost + (won - lost) / 2;
+        match simulate(mid) {
+            Some(mid_outcome) => {
+                won = mid;
+                outcome = mid_outcome;
+            },
+            None => lost = mid,
+        }
+    }
+
+    PowerSearch {
+        attack_power: won,
+        outcome,
+        simulations,
+    }
+}
+
+impl std::fmt::Display for PowerSearch {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            fmt,
+            "Elf attack power {} found after {} simulations",
+            self.attack_power, self.simulations
+        )?;
+        write!(fmt, "{}", self.outcome)
+    }
+}
+
+fn part2(initial_state: &[Vec<Cell>]) -> Scored<PowerSearch> {
+    let search = search_attack_power(initial_state);
+    Scored {
+        score: search.outcome.score(),
+        details: search,
+    }
+}
+
+pub struct Day15;
+
+impl Solution for Day15 {
+    type Input = Vec<Vec<Cell>>;
+    type Part1 = Scored<BattleOutcome>;
+    type Part2 = Scored<PowerSearch>;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Vec<Cell>> { parse_input(input) }
+
+    fn part1(initial_state: &Vec<Vec<Cell>>) -> Scored<BattleOutcome> { part1(initial_state) }
+
+    fn part2(initial_state: &Vec<Vec<Cell>>) -> Scored<PowerSearch> { part2(initial_state) }
+
+    fn report_part1(answer: &Scored<BattleOutcome>) -> Option<String> {
+        Some(answer.details.to_string())
+    }
+
+    fn report_part2(answer: &Scored<PowerSearch>) -> Option<String> {
+        Some(answer.details.to_string())
+    }
+}
+
+#[test]
+fn first_example_rounds() {
+    let map = parse_input("#######\n#.G...#\n#...EG#\n#.#.#G#\n#..G#E#\n#.....#\n#######");
+    let mut battle = Battle::new(&map, Stats::default(), Stats::default());
+
+    assert!(battle.step_round());
+    assert_eq!(
+        battle.to_string(),
+        "#######\n#..G..#   G(200)\n#...EG#   E(197), G(197)\n#.#G#G#   G(200), G(197)\n#...#E#   \
+         E(197)\n#.....#\n#######\n"
+    );
+
+    assert!(battle.step_round());
+    assert_eq!(
+        battle.to_string(),
+        "#######\n#...G.#   G(200)\n#..GEG#   G(200), E(188), G(194)\n#.#.#G#   G(194)\n#...#E#   \
+         E(194)\n#.....#\n#######\n"
+    );
+
+    while battle.step_round() {}
+    assert!(battle.is_finished());
+    assert_eq!(battle.rounds(), 47);
+    assert_eq!(battle.hitpoint_sum(), 590);
+    assert_eq!(battle.outcome().score(), 27730);
+
+    let outcome = battle.outcome();
+    assert_eq!(outcome.winner, Some(Faction::Goblin));
+    assert_eq!(outcome.elf_casualties, 2);
+    let mut survivor_hp: Vec<usize> = outcome.survivors.iter().map(|u| u.hit_points).collect();
+    survivor_hp.sort();
+    assert_eq!(survivor_hp, vec![59, 131, 200, 200]);
+    // The elf next to the goblins dies in round 23 and the other one in the final full round
+    assert_eq!(outcome.kills, vec![
+        Kill {
+            round: 23,
+            killer: 2,
+            victim: 1,
+            victim_faction: Faction::Elf,
+            position: (4, 2),
+        },
+        Kill {
+            round: 47,
+            killer: 4,
+            victim: 5,
+            victim_faction: Faction::Elf,
+            position: (5, 4),
+        },
+    ]);
+}
+
+#[test]
+fn outcome_report() {
+    let elves = Stats {
+        attack_power: 100,
+        hit_points: 150,
+    };
+    let mut battle = Battle::new(&parse_input("#####\n#E.G#\n#####"), elves, Stats::default());
+    while battle.step_round() {}
+
+    assert_eq!(
+        battle.outcome().to_string(),
+        "Elves win after 2 full rounds with 147 hit points left; score 294\nElf casualties: \
+         0\nSurvivors:\n  elf #0 at 2,1 with 147 hit points\nKills:\n  round 2: elf #0 killed \
+         goblin #1 at 3,1\n"
+    );
+}
+
+#[test]
+fn map_errors() {
+    assert_eq!(parse_map("#.#\n\n#E#\n").unwrap().len(), 2);
+    assert_eq!(
+        parse_map("###\n#x#\n###"),
+        Err("2:2: Invalid square `x`".to_owned())
+    );
+    assert_eq!(
+        parse_map("###\n#E# \n###"),
+        Err("2:4: Invalid square ` `".to_owned())
+    );
+    assert_eq!(
+        parse_map("###\n#E\n###"),
+        Err("2: Expected 3 squares like the rows before, found 2".to_owned())
+    );
+}
+
+#[test]
+fn stalemates() {
+    for &map in &["###\n#.#\n###", "#####\n#E#G#\n#####"] {
+        let mut battle = Battle::new(&parse_input(map), Stats::default(), Stats::default());
+        assert!(!battle.step_round(), "\n{}", map);
+        assert!(battle.is_finished());
+        assert!(!battle.step_round());
+
+        let outcome = battle.outcome();
+        assert_eq!((outcome.winner, outcome.rounds), (None, 0));
+        assert_eq!(solve(&parse_input(map), 3, false), Some(outcome));
+    }
+}
+
+#[test]
+fn unit_table() {
+    let map = parse_input("#######\n#.G...#\n#...EG#\n#.#.#G#\n#..G#E#\n#.....#\n#######");
+    let elves = Stats {
+        attack_power: 4,
+        hit_points: 100,
+    };
+    let mut battle = Battle::new(&map, elves, Stats::default());
+
+    let units: Vec<_> = battle
+        .units()
+        .iter()
+        .map(|unit| (unit.id, unit.faction, unit.position, unit.attack_power))
+        .collect();
+    assert_eq!(units, vec![
+        (0, Faction::Goblin, (2, 1), 3),
+        (1, Faction::Elf, (4, 2), 4),
+        (2, Faction::Goblin, (5, 2), 3),
+        (3, Faction::Goblin, (5, 3), 3),
+        (4, Faction::Goblin, (3, 4), 3),
+        (5, Faction::Elf, (5, 4), 4),
+    ]);
+
+    battle.step_round();
+    let moved: Vec<_> = battle.units().iter().map(|unit| unit.position).collect();
+    assert_eq!(moved, vec![(3, 1), (4, 2), (5, 2), (5, 3), (3, 3), (5, 4)]);
+    assert_eq!(battle.units()[1].hit_points, 100 - 3);
+
+    while battle.step_round() {}
+    assert_eq!(battle.elf_count(), 0);
+    assert_eq!(battle.elf_deaths(), 2);
+    assert!(battle
+        .units()
+        .iter()
+        .all(|unit| unit.is_alive() == (unit.faction == Faction::Goblin)));
+}
+
+#[test]
+fn example_outcomes() {
+    let examples = [
+        (
+            "#######\n#E..EG#\n#.#G.E#\n#E.##E#\n#G..#.#\n#..E#.#\n#######",
+            46,
+            859,
+        ),
+        (
+            "#######\n#E.G#.#\n#.#G..#\n#G.#.G#\n#G..#.#\n#...E.#\n#######",
+            35,
+            793,
+        ),
+        (
+            "#########\n#G......#\n#.E.#...#\n#..##..G#\n#...##..#\n#...#...#\n#.G...G.#\n#.....G.\
+             #\n#########",
+            20,
+            937,
+        ),
+    ];
+
+    for &(map, rounds, hitpoint_sum) in &examples {
+        let mut battle = Battle::new(&parse_input(map), Stats::default(), Stats::default());
+        while battle.step_round() {}
+        assert_eq!(
+            (battle.rounds(), battle.hitpoint_sum()),
+            (rounds, hitpoint_sum),
+            "\n{}",
+            map
+        );
+    }
+}
+
+#[test]
+fn attack_power_search() {
+    let examples = [
+        (
+            "#######\n#.G...#\n#...EG#\n#.#.#G#\n#..G#E#\n#.....#\n#######",
+            15,
+            4988,
+            8,
+        ),
+        (
+            "#######\n#E..EG#\n#.#G.E#\n#E.##E#\n#G..#.#\n#..E#.#\n#######",
+            4,
+            31284,
+            3,
+        ),
+        (
+            "#######\n#E.G#.#\n#.#G..#\n#G.#.G#\n#G..#.#\n#...E.#\n#######",
+            15,
+            3478,
+            8,
+        ),
+    ];
+
+    // 3, 6, 12 and 24 are tried before 18, 15, 13 and 14 for an attack power of 15, and 3 and 6
+    // before 4 for an attack power of 4
+    for &(map, attack_power, outcome, simulations) in &examples {
+        let search = search_attack_power(&parse_input(map));
+        assert_eq!(
+            (
+                search.attack_power,
+                search.outcome.score(),
+                search.simulations
+            ),
+            (attack_power, outcome, simulations),
+            "\n{}",
+            map
+        );
+    }
+}
+
+#[cfg(test)]
+fn manhattan_distance(x1: usize, y1: usize, x2: usize, y2: usize) -> usize {
+    let x_diff = if x1 < x2 { x2 - x1 } else { x1 - x2 };
+    let y_diff = if y1 < y2 { y2 - y1 } else { y1 - y2 };
+    x_diff + y_diff
+}
+
+#[cfg(test)]
+fn pathfind(
+    state: &[Vec<Cell>],
+    src_x: usize,
+    src_y: usize,
+    dst_x: usize,
+    dst_y: usize,
+) -> Option<Vec<(usize, usize)>> {
+    fringe(
+        &(src_x, src_y),
+        |&(x, y)| iter_neighbors(state, x, y, Cell::is_traversable).map(|n| (n, 1)),
+        |&(xt, yt)| manhattan_distance(xt, yt, dst_x, dst_y),
+        |&n| n == (dst_x, dst_y),
+    )
+    .map(|(path, _)| path)
+}
+
+/// The original movement: an A* search towards every square in range of an enemy, and
+/// then another from each neighbor towards the closest of them to pick the first step.
+#[cfg(test)]
+fn find_step_pathfind(state: &[Vec<Cell>], x: usize, y: usize) -> Option<(usize, usize)> {
+    let cell = state[y][x];
+
+    // move towards closest targets, stored as (x,y)
+    let mut possible_targets: Vec<(usize, usize)> = Vec::new();
+    for y2 in 0..state.len() {
+        for x2 in 0..state[y2].len() {
+            // find all enemies on the grid
+            let target = state[y2][x2];
+            if !cell.is_enemy(target) {
+                continue;
+            }
+
+            // valid targets are cells which are adjascent to an enemy and traversable
+            for (xa, ya) in iter_neighbors(state, x2, y2, Cell::is_traversable) {
+                possible_targets.push((xa as usize, ya as usize));
+            }
+        }
+    }
+    if possible_targets.is_empty() {
+        return None;
+    }
+
+    // Try the closest targets first in an effort to skip the inner loop of pathfinding
+    possible_targets.sort_unstable_by(|&(x1, y1), &(x2, y2)| {
+        let dst1 = manhattan_distance(x, y, x1, y1);
+        let dst2 = manhattan_distance(x, y, x2, y2);
+        dst1.cmp(&dst2)
+    });
+
+    let mut solutions = Vec::new();
+    let mut min_solution_len = usize::max_value();
+    let (mut min_target_x, mut min_target_y) = (usize::max_value(), usize::max_value());
+    for (target_x, target_y) in possible_targets {
+        // skip targets which are impossible to be closer than the current min
+        let min_possible_solution_len = manhattan_distance(x, y, target_x, target_y);
+        if min_possible_solution_len > min_solution_len {
+            continue;
+        }
+
+        if let Some(solution) = pathfind(state, x, y, target_x, target_y) {
+            let cur_solution_len = solution.len();
+            if cur_solution_len < min_solution_len
+                || ((cur_solution_len == min_solution_len)
+                    && ((target_y, target_x) < (min_target_y, min_target_x)))
+            {
+                min_solution_len = cur_solution_len;
+                min_target_x = target_x;
+                min_target_y = target_y;
+            } else {
+                // skip this target completely if it isn't the closest
+                continue;
+            }
+
+            // We've found a shortest solution, now check to see if there are multiple
+            // first steps that yield optimal paths and pick the first step with the
+            // first step which comes first in reading order
+            let (mut min_next_step_x, mut min_next_step_y) =
+                (usize::max_value(), usize::max_value());
+
+            for (xa, ya) in iter_neighbors(state, x, y, Cell::is_traversable) {
+                if let Some(solution) = pathfind(state, xa, ya, target_x, target_y) {
+                    if solution.len() >= min_solution_len {
+                        continue;
+                    }
+
+                    if (ya as usize, xa as usize) < (min_next_step_y, min_next_step_x) {
+                        min_next_step_x = xa as usize;
+                        min_next_step_y = ya as usize;
+                    }
+                }
+            }
+
+            solutions.push((
+                cur_solution_len,
+                (target_y, target_x),
+                (min_next_step_y, min_next_step_x),
+            ));
+        }
+    }
+
+    solutions.into_iter().min().map(|(_, _, (y, x))| (x, y))
+}
+
+/// The battle as it stood at the start of each of the first few rounds of the real input,
+/// along with the positions of every unit then
+#[cfg(test)]
+fn sample_turns(rounds: usize) -> Vec<(Battle, Vec<(usize, usize)>)> {
+    let mut battle = Battle::new(&parse_input(INPUT), Stats::default(), Stats::default());
+    (0..rounds)
+        .map(|_| {
+            let units = battle.living().map(|unit| unit.position).collect();
+            let before = battle.clone();
+            battle.step_round();
+            (before, units)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn find_step_bfs(battle: &Battle, x: usize, y: usize) -> Option<(usize, usize)> {
+    find_step(&battle.grid, &battle.units, x, y)
+}
+
+#[cfg(test)]
+fn find_step_astar(battle: &Battle, x: usize, y: usize) -> Option<(usize, usize)> {
+    find_step_pathfind(&battle.state(), x, y)
+}
+
+#[test]
+fn find_step_matches_pathfind() {
+    for (battle, units) in sample_turns(20) {
+        for (x, y) in units {
+            assert_eq!(find_step_bfs(&battle, x, y), find_step_astar(&battle, x, y));
+        }
+    }
+}
+
+#[cfg(test)]
+fn bench_find_step(
+    b: &mut test::Bencher,
+    find_step: fn(&Battle, usize, usize) -> Option<(usize, usize)>,
+) {
+    let turns = sample_turns(5);
+    b.iter(|| {
+        turns
+            .iter()
+            .flat_map(|(battle, units)| units.iter().map(move |&(x, y)| find_step(battle, x, y)))
+            .count()
+    })
+}
+
+#[bench]
+fn bench_find_step_bfs(b: &mut test::Bencher) { bench_find_step(b, find_step_bfs) }
+
+#[bench]
+fn bench_find_step_pathfind(b: &mut test::Bencher) { bench_find_step(b, find_step_astar) }
+
+#[bench]
+fn bench_p1(b: &mut test::Bencher) {
+    let initial_state = parse_input(INPUT);
+    b.iter(|| solve(&initial_state, 3, false).unwrap().score())
+}
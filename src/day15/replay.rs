@@ -0,0 +1,517 @@
+//! Recording day 15 battles round by round and playing them back in the terminal
+
+use std::{
+    fmt::{self, Display, Formatter, Write as FmtWrite},
+    io::{self, BufRead, Write},
+    str::FromStr,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+#[cfg(test)]
+use std::io::Cursor;
+
+#[cfg(test)]
+use super::{parse_input, Battle};
+use super::{write_state, Attack, Cell, Stats};
+
+/// The battlefield at the end of a round along with every attack made during it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The number of full rounds completed by the end of this frame.  Combat ends partway
+    /// through a round, so the last frame has the same count as the one before it.
+    pub round: usize,
+    pub state: Vec<Vec<Cell>>,
+    pub attacks: Vec<Attack>,
+}
+
+/// A recorded battle.  The first frame is the battlefield before anyone has taken a turn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub elves: Stats,
+    pub goblins: Stats,
+    pub frames: Vec<Frame>,
+}
+
+impl Replay {
+    pub fn new(elves: Stats, goblins: Stats) -> Self {
+        Replay {
+            elves,
+            goblins,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the first frame showing at least `round` full rounds, or of the last
+    /// frame if the battle ended before then.
+    pub fn seek(&self, round: usize) -> usize {
+        self.frames
+            .iter()
+            .position(|frame| frame.round >= round)
+            .unwrap_or_else(|| self.frames.len().saturating_sub(1))
+    }
+
+    fn max_hit_points(&self, cell: Cell) -> usize {
+        match cell {
+            Cell::Elf(_) => self.elves.hit_points,
+            _ => self.goblins.hit_points,
+        }
+    }
+}
+
+impl Display for Attack {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{} {},{} {},{} {}",
+            if self.killed { "kill" } else { "attack" },
+            self.attacker.0,
+            self.attacker.1,
+            self.target.0,
+            self.target.1,
+            self.damage
+        )
+    }
+}
+
+/// Replays are saved as a `#replay` header holding the elves' and then the goblins' attack
+/// power and hit points, followed by each frame as a `#frame <round>` line, its attacks and then
+/// the battlefield in the same format that `Battle` is displayed in.
+impl Display for Replay {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        writeln!(
+            fmt,
+            "#replay {} {} {} {}",
+            self.elves.attack_power,
+            self.elves.hit_points,
+            self.goblins.attack_power,
+            self.goblins.hit_points
+        )?;
+        for frame in &self.frames {
+            writeln!(fmt, "#frame {}", frame.round)?;
+            for attack in &frame.attacks {
+                writeln!(fmt, "{}", attack)?;
+            }
+            write_state(fmt, &frame.state)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn parse_number(token: &str) -> Result<usize, String> {
+    token
+        .parse()
+        .map_err(|_| format!("Expected a number, found `{}`", token))
+}
+
+fn parse_position(token: &str) -> Result<(usize, usize), String> {
+    let mut coords = token.splitn(2, ',');
+    match (coords.next(), coords.next()) {
+        (Some(x), Some(y)) => Ok((parse_number(x)?, parse_number(y)?)),
+        _ => Err(format!("Expected a position like `3,4`, found `{}`", token)),
+    }
+}
+
+fn parse_attack(tokens: &[&str]) -> Result<Attack, String> {
+    match tokens {
+        &[kind, attacker, target, damage] => Ok(Attack {
+            attacker: parse_position(attacker)?,
+            target: parse_position(target)?,
+            damage: parse_number(damage)?,
+            killed: kind == "kill",
+        }),
+        _ => Err("Expected an attacker, a target and the damage dealt".to_owned()),
+    }
+}
+
+/// Parses a row of the battlefield along with the hit points of the units on it
+fn parse_row(line: &str) -> Result<Vec<Cell>, String> {
+    let (squares, hit_points) = match line.find("   ") {
+        Some(ix) => (&line[..ix], line[ix..].trim()),
+        None => (line, ""),
+    };
+    let mut row = squares
+        .chars()
+        .map(|c| Cell::from_char(c).ok_or_else(|| format!("Invalid square `{}`", c)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut units = row.iter_mut().filter(|cell| cell.hit_points().is_some());
+    for entry in hit_points.split(", ").filter(|entry| !entry.is_empty()) {
+        let unit = units
+            .next()
+            .ok_or_else(|| format!("`{}` doesn't match any unit", entry))?;
+        let kind = unit.to_string();
+        // `kind` and the parentheses are ASCII, so checking for them first keeps the slice on
+        // character boundaries
+        let is_entry = entry.len() >= kind.len() + 2
+            && entry.starts_with(&kind)
+            && entry[kind.len()..].starts_with('(')
+            && entry.ends_with(')');
+        let hp = if is_entry {
+            parse_number(&entry[kind.len() + 1..entry.len() - 1])?
+        } else {
+            return Err(format!(
+                "Expected `{}(<hit points>)`, found `{}`",
+                kind, entry
+            ));
+        };
+        *unit = match unit {
+            Cell::Elf(_) => Cell::Elf(hp),
+            _ => Cell::Goblin(hp),
+        };
+    }
+    if units.next().is_some() {
+        return Err("Missing hit points for some units".to_owned());
+    }
+
+    Ok(row)
+}
+
+impl FromStr for Replay {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let mut replay: Option<Replay> = None;
+
+        for (line_ix, line) in s.lines().enumerate() {
+            let err = |message: String| ParseError {
+                line: line_ix + 1,
+                message,
+            };
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            let replay = match (replay.as_mut(), tokens.first()) {
+                (None, Some(&"#replay")) => {
+                    let stats = tokens[1..]
+                        .iter()
+                        .map(|token| parse_number(token))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(err)?;
+                    let (elves, goblins) = match stats.as_slice() {
+                        &[elf_attack, elf_hp, goblin_attack, goblin_hp] => (
+                            Stats {
+                                attack_power: elf_attack,
+                                hit_points: elf_hp,
+                            },
+                            Stats {
+                                attack_power: goblin_attack,
+                                hit_points: goblin_hp,
+                            },
+                        ),
+                        _ => return Err(err("Expected four stats after `#replay`".to_owned())),
+                    };
+                    replay = Some(Replay::new(elves, goblins));
+                    continue;
+                },
+                (None, _) => return Err(err("Expected `#replay`".to_owned())),
+                (Some(replay), _) => replay,
+            };
+
+            match tokens.first() {
+                None => (),
+                Some(&"#frame") => {
+                    let round = match tokens.get(1..) {
+                        Some(&[round]) => parse_number(round).map_err(err)?,
+                        _ => return Err(err("Expected the round after `#frame`".to_owned())),
+                    };
+                    replay.frames.push(Frame {
+                        round,
+                        state: Vec::new(),
+                        attacks: Vec::new(),
+                    });
+                },
+                Some(&first) => {
+                    let frame = replay
+                        .frames
+                        .last_mut()
+                        .ok_or_else(|| err("Expected `#frame`".to_owned()))?;
+                    if first == "attack" || first == "kill" {
+                        frame.attacks.push(parse_attack(&tokens).map_err(err)?);
+                    } else {
+                        frame.state.push(parse_row(line).map_err(err)?);
+                    }
+                },
+            }
+        }
+
+        replay.ok_or_else(|| ParseError {
+            line: 1,
+            message: "Expected `#replay`".to_owned(),
+        })
+    }
+}
+
+const CLEAR: &str = "\x1b[2J\x1b[H";
+const RESET: &str = "\x1b[0m";
+const GREY: &str = "\x1b[90m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const INVERT: &str = "\x1b[7m";
+
+/// The width of the hit point bars shown next to each row
+const HP_BAR_WIDTH: usize = 10;
+
+/// Keeps the time each frame is shown for within reach of the speed controls
+fn clamp_delay(delay: Duration) -> Duration {
+    delay
+        .max(Duration::from_millis(10))
+        .min(Duration::from_secs(10))
+}
+
+const CONTROLS: &str = "enter/p: pause  +/-: speed  n/b: step  g <round>: seek  q: quit";
+
+fn color(cell: Cell) -> &'static str {
+    match cell {
+        Cell::Elf(_) => GREEN,
+        Cell::Goblin(_) => RED,
+        Cell::Wall => GREY,
+        Cell::Blank => "",
+    }
+}
+
+fn render(replay: &Replay, ix: usize, delay: Duration, paused: bool, message: &str) -> String {
+    let frame = &replay.frames[ix];
+    let last = replay.frames.len() - 1;
+    let status = if ix == last {
+        " - combat over"
+    } else if paused {
+        " - paused"
+    } else {
+        ""
+    };
+    let delay_ms = delay.as_secs() * 1000 + u64::from(delay.subsec_millis());
+
+    let mut out = CLEAR.to_owned();
+    let _ = writeln!(
+        out,
+        "Round {} (frame {}/{}){}  {}ms per frame",
+        frame.round, ix, last, status, delay_ms
+    );
+
+    for (y, row) in frame.state.iter().enumerate() {
+        for (x, &cell) in row.iter().enumerate() {
+            let hit = frame
+                .attacks
+                .iter()
+                .rev()
+                .find(|attack| attack.target == (x, y));
+            match hit {
+                Some(attack) if attack.killed && cell == Cell::Blank =>
+                    out.push_str(&format!("{}x{}", YELLOW, RESET)),
+                Some(_) => out.push_str(&format!("{}{}{}{}", color(cell), INVERT, cell, RESET)),
+                None if cell == Cell::Blank => out.push('.'),
+                None => out.push_str(&format!("{}{}{}", color(cell), cell, RESET)),
+            }
+        }
+
+        for &cell in row {
+            if let Some(hp) = cell.hit_points() {
+                let max = replay.max_hit_points(cell).max(1);
+                let filled = ((hp * HP_BAR_WIDTH + max - 1) / max).min(HP_BAR_WIDTH);
+                let _ = write!(
+                    out,
+                    "  {}{} [{}{}] {:>3}{}",
+                    color(cell),
+                    cell,
+                    "=".repeat(filled),
+                    " ".repeat(HP_BAR_WIDTH - filled),
+                    hp,
+                    RESET
+                );
+            }
+        }
+        out.push('\n');
+    }
+
+    let kills = frame.attacks.iter().filter(|attack| attack.killed).count();
+    let _ = writeln!(
+        out,
+        "\n{} attacks, {} kills this round\n{}",
+        frame.attacks.len(),
+        kills,
+        CONTROLS
+    );
+    if !message.is_empty() {
+        let _ = writeln!(out, "{}", message);
+    }
+    out
+}
+
+/// Plays `replay` back on `out`, starting with each frame shown for `delay`, which is kept between
+/// 10ms and 10s.  Controls are read
+/// a line at a time from `input` on a separate thread so that playback carries on while waiting
+/// for them, and playback finishes once `input` runs out and the last frame has been shown.
+pub fn play<I: BufRead + Send + 'static>(
+    replay: &Replay,
+    input: I,
+    mut out: impl Write,
+    mut delay: Duration,
+) -> io::Result<()> {
+    if replay.frames.is_empty() {
+        return Ok(());
+    }
+    delay = clamp_delay(delay);
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in input.lines() {
+            match line {
+                Ok(line) =>
+                    if tx.send(line).is_err() {
+                        break;
+                    },
+                Err(_) => break,
+            }
+        }
+    });
+
+    let last = replay.frames.len() - 1;
+    let mut ix = 0;
+    let mut paused = false;
+    let mut message = String::new();
+    loop {
+        write!(out, "{}", render(replay, ix, delay, paused, &message))?;
+        out.flush()?;
+        message.clear();
+
+        let line = if paused || ix == last {
+            match rx.recv() {
+                Ok(line) => line,
+                Err(_) => break,
+            }
+        } else {
+            match rx.recv_timeout(delay) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    ix += 1;
+                    continue;
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    thread::sleep(delay);
+                    ix += 1;
+                    continue;
+                },
+            }
+        };
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None | Some("p") => {
+                paused = !paused;
+                if !paused && ix == last {
+                    ix = 0;
+                }
+            },
+            Some("+") => delay = clamp_delay(delay / 2),
+            Some("-") => delay = clamp_delay(delay * 2),
+            Some("n") => {
+                ix = (ix + 1).min(last);
+                paused = true;
+            },
+            Some("b") => {
+                ix = ix.saturating_sub(1);
+                paused = true;
+            },
+            Some("g") => match words.next().map(parse_number) {
+                Some(Ok(round)) => ix = replay.seek(round),
+                Some(Err(msg)) => message = msg,
+                None => message = "Missing round".to_owned(),
+            },
+            Some("q") => break,
+            Some(command) => message = format!("Unknown command `{}`", command),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn record_example() -> Replay {
+    let map = parse_input("#######\n#.G...#\n#...EG#\n#.#.#G#\n#..G#E#\n#.....#\n#######");
+    let mut battle = Battle::new(&map, Stats::default(), Stats::default());
+    battle.enable_recording();
+    while battle.step_round() {}
+    battle.replay().unwrap().clone()
+}
+
+#[test]
+fn round_trip() {
+    let replay = record_example();
+    assert_eq!(replay.frames.len(), 49);
+    assert_eq!(replay.frames[48].round, 47);
+    assert_eq!(replay.seek(2), 2);
+    assert_eq!(replay.seek(100), 48);
+
+    let parsed: Replay = replay.to_string().parse().unwrap();
+    assert_eq!(parsed, replay);
+
+    let err = "#replay 3 200 3 200\n#frame 0\n#.E#   E(200)\nkill 1,1 x 3\n"
+        .parse::<Replay>()
+        .unwrap_err();
+    assert_eq!(err.line, 4);
+
+    for &row in &[
+        "#E#   E)",
+        "#E#   Ex5)",
+        "#E#   E\u{e9}5)",
+        "#E#   G(5)",
+        "#E#   E(x)",
+    ] {
+        let err = format!("#replay 3 200 3 200\n#frame 0\n{}\n", row)
+            .parse::<Replay>()
+            .unwrap_err();
+        assert_eq!(err.line, 3, "{}", row);
+    }
+}
+
+#[test]
+fn playback_controls() {
+    let replay = record_example();
+    let input = Cursor::new(b"g 2\nn\nq\n".to_vec());
+    let mut out = Vec::new();
+    play(&replay, input, &mut out, Duration::from_secs(60)).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let frames: Vec<&str> = out.split(CLEAR).skip(1).collect();
+    assert_eq!(frames.len(), 3);
+    assert!(frames[1].starts_with("Round 2 (frame 2/48)"));
+    assert!(frames[2].starts_with("Round 3 (frame 3/48) - paused"));
+
+    // The delay stops at its limits and can be brought back from them
+    let controls = format!("p\n{}{}+\n-\nq\n", "+\n".repeat(20), "-\n".repeat(70));
+    let mut out = Vec::new();
+    play(
+        &replay,
+        Cursor::new(controls.into_bytes()),
+        &mut out,
+        Duration::from_secs(60),
+    )
+    .unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let delays: Vec<&str> = out
+        .split(CLEAR)
+        .skip(1)
+        .map(|frame| frame.lines().next().unwrap().rsplit("  ").next().unwrap())
+        .collect();
+    assert_eq!(delays[0], "10000ms per frame");
+    assert_eq!(delays[21], "10ms per frame");
+    assert_eq!(delays[22], "20ms per frame");
+    assert_eq!(delays[91], "10000ms per frame");
+    assert_eq!(delays[92], "5000ms per frame");
+    assert_eq!(delays[93], "10000ms per frame");
+}
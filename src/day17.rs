@@ -2,6 +2,8 @@ use std::usize;
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 const INPUT: &str = include_str!("../input/day17.txt");
 
 lazy_static! {
@@ -9,7 +11,10 @@ lazy_static! {
     static ref Y_RGX: Regex = Regex::new("y=(\\d+)(?:\\.\\.(\\d+))?").unwrap();
 }
 
-fn parse_line(line: &str) -> ((usize, Option<usize>), (usize, Option<usize>)) {
+/// `((x, x_end), (y, y_end))` of a single vein of clay
+type Vein = ((usize, Option<usize>), (usize, Option<usize>));
+
+fn parse_line(line: &str) -> Vein {
     let x_caps = X_RGX.captures(line).unwrap();
     let x = (
         x_caps[1].parse().unwrap(),
@@ -24,8 +29,12 @@ fn parse_line(line: &str) -> ((usize, Option<usize>), (usize, Option<usize>)) {
     (x, y)
 }
 
-fn parse_input() -> impl Iterator<Item = ((usize, Option<usize>), (usize, Option<usize>))> {
-    INPUT.lines().filter(|l| !l.is_empty()).map(parse_line)
+fn parse_input(input: &str) -> Vec<Vein> {
+    input
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(parse_line)
+        .collect()
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -150,8 +159,8 @@ fn spread_right(
     spread_right(world, x + 1, y, ignore_water)
 }
 
-fn compute_world() -> (usize, usize, Vec<Vec<Cell>>) {
-    let (max_x, max_y, min_y) = parse_input().fold(
+fn compute_world(veins: &[Vein]) -> (usize, usize, Vec<Vec<Cell>>) {
+    let (max_x, max_y, min_y) = veins.iter().fold(
         (0, 0, usize::max_value()),
         |(max_x, max_y, min_y), (x, y)| {
             let cur_max_x = (x.0).max((x.1).unwrap_or(0));
@@ -161,7 +170,7 @@ fn compute_world() -> (usize, usize, Vec<Vec<Cell>>) {
     );
 
     let mut world = vec![vec![Cell::Sand; max_x + 2]; max_y + 1];
-    for (x, y) in parse_input() {
+    for &(x, y) in veins {
         if let Some(max_x) = x.1 {
             for x in (x.0)..=max_x {
                 world[y.0][x] = Cell::Clay;
@@ -187,13 +196,13 @@ fn count_water(world: &[Vec<Cell>], min_y: usize, max_y: usize) -> usize {
         .count()
 }
 
-fn part1() -> usize {
-    let (min_y, max_y, world) = compute_world();
+fn part1(veins: &[Vein]) -> usize {
+    let (min_y, max_y, world) = compute_world(veins);
     count_water(&world, min_y, max_y)
 }
 
-fn part2() -> usize {
-    let (min_y, max_y, mut world) = compute_world();
+fn part2(veins: &[Vein]) -> usize {
+    let (min_y, max_y, mut world) = compute_world(veins);
 
     let mut count = count_water(&world, min_y, max_y);
     let row_len = world[0].len();
@@ -233,7 +242,18 @@ fn part2() -> usize {
     }
 }
 
-pub fn run() {
-    println!("Part 1: {:?}", part1());
-    println!("Part 2: {:?}", part2());
+pub struct Day17;
+
+impl Solution for Day17 {
+    type Input = Vec<Vein>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Vein> { parse_input(input) }
+
+    fn part1(veins: &Vec<Vein>) -> usize { part1(veins) }
+
+    fn part2(veins: &Vec<Vein>) -> usize { part2(veins) }
 }
@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
+use crate::solution::Solution;
+
 const INPUT: &str = include_str!("../input/day18.txt");
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
-enum Cell {
+pub enum Cell {
     Ground,
     Trees,
     Lumberyard,
@@ -40,8 +42,8 @@ impl Cell {
     }
 }
 
-fn parse_input() -> Vec<Vec<Cell>> {
-    INPUT
+fn parse_input(input: &str) -> Vec<Vec<Cell>> {
+    input
         .lines()
         .filter(|l| !l.is_empty())
         .map(|l| {
@@ -119,8 +121,8 @@ fn compute_solution(world: &[Vec<Cell>]) -> usize {
     tree_count * lumberyard_count
 }
 
-fn part1() -> usize {
-    let mut world = parse_input();
+fn part1(world: &[Vec<Cell>]) -> usize {
+    let mut world = world.to_vec();
     for _ in 0..10 {
         world = tick(world);
     }
@@ -150,8 +152,8 @@ fn find_cycle(mut state: State) -> (usize, usize, State) {
     (first_cycle_tick, first_repeat_tick, state)
 }
 
-fn part2() -> usize {
-    let state = parse_input();
+fn part2(state: &[Vec<Cell>]) -> usize {
+    let state = state.to_vec();
 
     let (first_cycle_tick, first_repeat_tick, mut new_state) = find_cycle(state);
     let cycle_length = first_repeat_tick - first_cycle_tick;
@@ -167,7 +169,7 @@ fn part2() -> usize {
 
 #[test]
 fn test_cycle_durability() {
-    let state = parse_input();
+    let state = parse_input(INPUT);
     let (first_cycle_tick, first_repeat_tick, cycle_init_state) = find_cycle(state.clone());
     let cycle_length = first_repeat_tick - first_cycle_tick;
     println!("Cycle length: {}", cycle_length);
@@ -181,7 +183,18 @@ fn test_cycle_durability() {
     }
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day18;
+
+impl Solution for Day18 {
+    type Input = Vec<Vec<Cell>>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Vec<Cell>> { parse_input(input) }
+
+    fn part1(world: &Vec<Vec<Cell>>) -> usize { part1(world) }
+
+    fn part2(world: &Vec<Vec<Cell>>) -> usize { part2(world) }
 }
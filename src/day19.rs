@@ -1,137 +1,91 @@
-use std::str::FromStr;
-
-use crate::asm_common::*;
+use crate::{asm_common::*, optimizer::optimize, solution::Solution};
 
 const INPUT: &str = include_str!("../input/day19.txt");
 
-use regex::Regex;
+fn parse_input(input: &str) -> VM {
+    let program: Program = input
+        .parse()
+        .unwrap_or_else(|err| panic!("Invalid program: {}", err));
 
-lazy_static! {
-    static ref RGX: Regex = Regex::new("(?:\\d+:)?(\\w+) (\\d+) (\\d+) (\\d+)").unwrap();
+    VM::new(&program)
 }
 
-fn parse_input() -> (Registers, impl Iterator<Item = Instruction>) {
-    let mut reg = Registers::new(None);
-
-    let mut lines = INPUT.lines();
-    let ip_decl_line = lines.next().unwrap();
-    reg.ip_register = parse_ip_decl(ip_decl_line);
-
-    let iter = lines.filter_map(|l| {
-        if l.is_empty() || l.starts_with(';') {
-            return None;
-        }
-
-        let cap = RGX.captures(l).unwrap();
-        let instr = Instruction {
-            opcode: Opcode::from_str(&cap[1]).unwrap(),
-            in1: cap[2].parse().unwrap(),
-            in2: cap[3].parse().unwrap(),
-            out: cap[4].parse().unwrap(),
-        };
-        Some(instr)
-    });
-
-    (reg, iter)
-}
+fn part1(vm: &VM) -> usize {
+    let mut vm = vm.clone();
 
-fn part1() -> usize {
-    let (registers, instructions_iter) = parse_input();
-    let mut vm = VM {
-        regs: registers,
-        instructions: Instructions {
-            ip: 0usize,
-            instructions: instructions_iter.collect(),
-        },
-    };
-
-    vm.run();
+    vm.run()
+        .unwrap_or_else(|err| panic!("{} at instruction {}", err, vm.ip()));
     // println!("Registers after running: {:?}", vm.regs);
 
     vm.regs.regs[0]
 }
 
-fn get_all_factors(n: usize) -> impl Iterator<Item = usize> {
-    (1..=n).filter(move |factor| n % factor == 0)
+/// Runs the program until its setup is done, returning the number whose divisors are summed.  The
+/// optimizer finds the loop doing the sum, and the setup is over once execution first reaches that
+/// loop's header, so the number is whatever is in its target register at that point.
+fn find_target(vm: &VM) -> usize {
+    let mut vm = vm.clone();
+    optimize(&mut vm.instructions, vm.regs.ip_register);
+
+    let (header, target) = vm
+        .instructions
+        .fused
+        .iter()
+        .enumerate()
+        .filter_map(|(ip, fused)| match *fused {
+            Some(Fused::DivisorSum { target, .. }) => Some((ip, target)),
+            _ => None,
+        })
+        .next()
+        .expect("The program doesn't sum divisors");
+
+    while vm.ip() != header {
+        assert_eq!(vm.tick(), Ok(false), "The program halted during setup");
+    }
+
+    vm.regs.regs[target]
 }
 
-/// The algorithm works roughly like this:
-///   1. Register 4 is populated with some number by doing some random math using register 1
-///   2. The sum of all factors of the number in register 4 is accumulated in register [0].  This
-///      includes the (1, n) factor pair.
-///   3. The program halts, leaving the accumulated sum in register 0
-fn part2() -> usize {
-    // #ip 1
-    // 0: addi 1 16 1; jmp 17
-    // 1: seti 1 1 3; [3] = 1
-    // ;;; initial regs: [1, 33, 10550400, 0, 10551370, 1]
-    // ;;;
-    // ;;; [0] = 1;
-    // ;;; [5] = 1;
-    // ;;; [4] = 10551370;
-    // ;;; [3] = 0;
-    // ;;;
-    // ;;; LBL b:
-    // ;;; [5] = 1
-    // ;;; LBL a:
-    // ;;; if ([3] * [5] == [4]):
-    // ;;;    [0] += [3]
-    // ;;; [5] += 1
-    // ;;; if [5] <= [4]:
-    // ;;;    goto a;
-    // ;;;
-    // ;;; [3] += 1
-    // ;;; if [3] <= [4]:
-    // ;;;    goto b
-    // ;;; HALT
-    // ;;;
-    // ;;;
-    // ;;;
-    // 2: seti 1 9 5; [5] = 1
-    // 3: mulr 3 5 2; [2] = [3] * [5]
-    // 4: eqrr 2 4 2; [2] = [2] == [4]
-    // 5: addr 2 1 1; jmp (ip + [2])
-    // 6: addi 1 1 1; jmp (ip + 1)
-    // 7: addr 3 0 0; [0] += [3]
-    // ;;; if ([2] * [3] == [4]), [0] += [3]
-    // 8: addi 5 1 5; [5] += 1
-    // ;;; if [5] <= [4], jmp 3
-    // 9: gtrr 5 4 2; [2] = [5] > [4]
-    // 10: addr 1 2 1; if ![2]:
-    // 11: seti 2 6 1;    jmp 3
-    // 12: addi 3 1 3; [3] += 1
-    // ;;; [3] += 3; if [3] <= 4, jmp 2
-    // 13: gtrr 3 4 2; [2] = [3] > [4]
-    // 14: addr 2 1 1; if ![2]:
-    // 15: seti 1 6 1;    jmp 2
-    // ;;; while [3] <= [4], [3] += 1
-    // 16: mulr 1 1 1; HALT
-    // 17: addi 4 2 4; [4] += 2
-    // 18: mulr 4 4 4; [4] *= [4]
-    // 19: mulr 1 4 4; [4] *= ip
-    // 20: muli 4 11 4; [4] *= 11
-    // 21: addi 2 6 2; [2] += 6
-    // 22: mulr 2 1 2; [2] *= ip
-    // 23: addi 2 2 2; [2] += 2
-    // 24: addr 4 2 4; [4] += [2]
-    // ;;; will halt if [0] >= 10
-    // 25: addr 1 0 1; jmp (ip + [0])
-    // 26: seti 0 3 1; jmp 1
-    // 27: setr 1 4 2; [2] = [1]
-    // 28: mulr 2 1 2; [2] *= [1]
-    // 29: addr 1 2 2; [2] += [1]
-    // 30: mulr 1 2 2; [2] *= [1]
-    // 31: muli 2 14 2; [2] *= 2
-    // 32: mulr 2 1 2; [2] *= [1]
-    // 33: addr 4 2 4; [4] += [4]
-    // 34: seti 0 0 0; [0] = 0
-    // 35: seti 0 4 1; jmp 1
-
-    let number = 10_551_370;
-    get_all_factors(number).sum()
+fn sum_of_divisors(n: usize) -> usize {
+    (1..)
+        .take_while(|i| i * i <= n)
+        .filter(|i| n % i == 0)
+        .map(|i| if i * i == n { i } else { i + n / i })
+        .sum()
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+/// The program sums all divisors of a number that it computes first, which is much larger in part
+/// 2. That takes far too long to interpret, so only the setup is run.
+fn part2(vm: &VM) -> usize {
+    let mut vm = vm.clone();
+    vm.regs[0] = 1;
+
+    sum_of_divisors(find_target(&vm))
+}
+
+pub struct Day19;
+
+impl Solution for Day19 {
+    type Input = VM;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> VM { parse_input(input) }
+
+    fn part1(vm: &VM) -> usize { part1(vm) }
+
+    fn part2(vm: &VM) -> usize { part2(vm) }
+}
+
+#[test]
+fn divisor_sum() {
+    assert_eq!(sum_of_divisors(1), 1);
+    assert_eq!(sum_of_divisors(36), 91);
+    assert_eq!(sum_of_divisors(10_551_370), 18_992_484);
+
+    // Part 1's number is small enough to check against the interpreter
+    let vm = parse_input(INPUT);
+    assert_eq!(sum_of_divisors(find_target(&vm)), part1(&vm));
 }
@@ -1,28 +1,39 @@
-extern crate libc;
 extern crate packed_simd;
 extern crate test;
 
-use std::{collections::HashMap, hint::unreachable_unchecked, intrinsics::likely, mem, slice};
+use std::{collections::HashMap, intrinsics::likely};
 
-use packed_simd::{m8, m8x32, u8x32};
+use packed_simd::{m8x32, u8x32};
+
+use crate::solution::Solution;
 
 const INPUT: &str = include_str!("../input/day2.txt");
-// We include 5 bytes of *undefined* memory in order to have the thing padded to 27-byte windows,
-// which is necessary for the way that we read them into SIMD registers later.
-const INPUT_BYTES: [u8; 6750] = *include_bytes!("../input/day2.txt");
-const LINE_COUNT: usize = 250;
-const LINE_LENGTH_WITH_NEWLINE: usize = 27;
-
-fn parse_input() -> impl Iterator<Item = &'static [u8]> {
-    INPUT.lines().map(|line| line.as_bytes())
+const LINE_LENGTH: usize = 26;
+
+fn parse_input(input: &str) -> Vec<[u8; LINE_LENGTH]> {
+    input
+        .lines()
+        .map(|line| {
+            assert_eq!(
+                line.len(),
+                LINE_LENGTH,
+                "Box IDs must be {} characters long: `{}`",
+                LINE_LENGTH,
+                line
+            );
+            let mut id = [0u8; LINE_LENGTH];
+            id.copy_from_slice(line.as_bytes());
+            id
+        })
+        .collect()
 }
 
-fn part1() -> usize {
+fn part1(ids: &[[u8; LINE_LENGTH]]) -> usize {
     let mut seen_twice = 0usize;
     let mut seen_three_times = 0usize;
     let mut seen_letters: HashMap<u8, usize> = HashMap::new();
 
-    for line in parse_input() {
+    for line in ids {
         for c in line {
             seen_letters.entry(*c).and_modify(|i| *i += 1).or_insert(1);
         }
@@ -43,62 +54,18 @@ fn part1() -> usize {
     seen_twice * seen_three_times
 }
 
-#[inline(always)]
-fn get_shift_mask() -> m8x32 {
-    [
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(true),
-        m8::new(false),
-        m8::new(false),
-        m8::new(false),
-        m8::new(false),
-        m8::new(false),
-    ]
-    .into()
-}
-
 #[inline(never)]
-fn part2() {
-    let shift_mask: m8x32 = get_shift_mask();
-
-    let mut parsed_input: [u8x32; LINE_COUNT] = unsafe { mem::uninitialized() };
-    for (i, slot) in parsed_input.iter_mut().enumerate() {
-        let input_offset = i * LINE_LENGTH_WITH_NEWLINE;
-        let p = slot as *mut packed_simd::Simd<[u8; 32]>;
-
-        let line_slice =
-            unsafe { slice::from_raw_parts((&INPUT_BYTES as *const u8).add(input_offset), 32) };
-        let packed_line = unsafe { u8x32::from_slice_unaligned_unchecked(line_slice) };
-        // replace the last 5 bits with zeroes
-        let packed_shifted_line = shift_mask.select(packed_line, u8x32::splat(0));
-
-        unsafe { p.write(packed_shifted_line) };
-    }
+fn part2(ids: &[[u8; LINE_LENGTH]]) -> String {
+    // Each ID is zero-padded to a full 32-byte vector.  The padding is equal for every line, so
+    // it never counts as a difference.
+    let parsed_input: Vec<u8x32> = ids
+        .iter()
+        .map(|id| {
+            let mut padded = [0u8; 32];
+            padded[..LINE_LENGTH].copy_from_slice(id);
+            u8x32::from_slice_unaligned(&padded)
+        })
+        .collect();
 
     let zeroes = u8x32::splat(0u8);
     let ones = u8x32::splat(1u8);
@@ -114,26 +81,38 @@ fn part2() {
                 let line1_chars: [u8; 32] = (*line).into();
                 let line2_chars: [u8; 32] = (*line2).into();
 
-                for (i, c) in line1_chars[0..27].iter().enumerate() {
+                let mut common_chars = String::with_capacity(LINE_LENGTH - 1);
+                for (i, c) in line1_chars[0..LINE_LENGTH].iter().enumerate() {
                     if unsafe { likely(*c == line2_chars[i]) } {
-                        unsafe { libc::putchar(libc::c_int::from(*c)) };
+                        common_chars.push(*c as char);
                     }
                 }
-                return;
+                return common_chars;
             }
         }
     }
 
-    unsafe { unreachable_unchecked() }
+    panic!("No two IDs differ by exactly one character")
 }
 
 #[bench]
-fn bench_part2(b: &mut test::Bencher) { b.iter(part2) }
+fn bench_part2(b: &mut test::Bencher) {
+    let ids = parse_input(INPUT);
+    b.iter(|| part2(&ids))
+}
+
+pub struct Day2;
+
+impl Solution for Day2 {
+    type Input = Vec<[u8; LINE_LENGTH]>;
+    type Part1 = usize;
+    type Part2 = String;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<[u8; LINE_LENGTH]> { parse_input(input) }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
+    fn part1(ids: &Vec<[u8; LINE_LENGTH]>) -> usize { part1(ids) }
 
-    print!("Part 2:");
-    print!("\n");
-    part2();
+    fn part2(ids: &Vec<[u8; LINE_LENGTH]>) -> String { part2(ids) }
 }
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
+use crate::solution::Solution;
+
 mod parser;
 
-const INPUT: &[u8] = include_bytes!("../../input/day20.txt");
+const INPUT: &str = include_str!("../../input/day20.txt");
 
 #[derive(Clone, Debug)]
 pub enum MovementDirection {
@@ -146,12 +148,10 @@ fn traverse_path(
     )
 }
 
-fn compute_distances() -> impl Iterator<Item = usize> {
-    let directions = parser::parse_input(INPUT);
-
+fn compute_distances(directions: &[MovementDirection]) -> impl Iterator<Item = usize> {
     let mut door_distances: HashMap<(isize, isize), usize> = HashMap::new();
     let _ = traverse_path(
-        directions.into_iter(),
+        directions.iter().cloned(),
         &mut door_distances,
         (0isize, 0isize),
         0,
@@ -163,10 +163,10 @@ fn compute_distances() -> impl Iterator<Item = usize> {
         .map(|(_coord, distance)| distance)
 }
 
-fn part1() -> usize { compute_distances().max().unwrap() }
+fn part1(directions: &[MovementDirection]) -> usize { compute_distances(directions).max().unwrap() }
 
-pub fn part2() -> usize {
-    compute_distances()
+pub fn part2(directions: &[MovementDirection]) -> usize {
+    compute_distances(directions)
         .filter(|&distance| distance >= 1000)
         .count()
 }
@@ -243,13 +243,30 @@ mod bench {
     extern crate test;
 
     #[bench]
-    fn bench_compute_distances(b: &mut test::Bencher) { b.iter(super::compute_distances) }
+    fn bench_compute_distances(b: &mut test::Bencher) {
+        let directions = super::parser::parse_input(super::INPUT.as_bytes());
+        b.iter(|| super::compute_distances(&directions))
+    }
 
     #[bench]
-    fn bench_p2(b: &mut test::Bencher) { b.iter(super::part2) }
+    fn bench_p2(b: &mut test::Bencher) {
+        let directions = super::parser::parse_input(super::INPUT.as_bytes());
+        b.iter(|| super::part2(&directions))
+    }
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day20;
+
+impl Solution for Day20 {
+    type Input = Vec<MovementDirection>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<MovementDirection> { parser::parse_input(input.as_bytes()) }
+
+    fn part1(directions: &Vec<MovementDirection>) -> usize { part1(directions) }
+
+    fn part2(directions: &Vec<MovementDirection>) -> usize { part2(directions) }
 }
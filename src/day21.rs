@@ -1,48 +1,182 @@
-fn part1() -> usize {
-    // Decompiled using:
-    // https://github.com/ttencate/aoc2018/blob/master/src/vm/decompiler.rs
-    //
-    // Register 0 is a.
-    //
-    //      e = 123;
-    //      do {
-    //          e &= 0x1c8;
-    //      } while e != 72;
-    //      e = 0;
-    //      do {
-    //          b = e | 0x10000;
-    //          e = 16031208;
-    //  8:      d = b & 0xff;
-    //          e += d;
-    //          // e &= 0xffffff;
-    //          e *= 65899;
-    //          // e &= 0xffffff;
-    //          if 256 <= b {
-    //              d = 0;
-    // 18:          f = d + 1;
-    //              f *= 256;
-    //              if f <= b {
-    //                  d += 1;
-    //                  goto 18;
-    //              }
-    //              b = d;
-    //              goto 8;
-    //          }
-    //      } while e != a;
-
-    // Solved by adding a statement to print out the contents of register e (4) during the `while`
-    // check, since that's the earliest point at which the program could halt.
-
-    10_720_163
-}
-
-fn part2() -> usize {
-    // I blatantly cheated (stole the algorithm from reddit), and I only kinda feel bad.
-    // https://www.reddit.com/r/adventofcode/comments/a86jgt/2018_day_21_solutions/ec8g4h2
-    5_885_821
-}
-
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+use std::collections::HashSet;
+
+use crate::{asm_common::*, optimizer::optimize, solution::Solution};
+
+const INPUT: &str = include_str!("../input/day21.txt");
+
+/// The program only reads register 0 in a single `eqrr` that halts the program if it's equal to
+/// another register:
+///
+/// ```text
+///      e = 0;
+///      do {
+///          b = e | 0x10000;
+///          e = <seed>;
+///          <mix the bytes of b into e>
+///      } while e != a;
+/// ```
+///
+/// The sequence of values that `e` takes on only depends on its previous value, so it eventually
+/// repeats.  Register 0 never changes, so the program only halts if it's set to one of the values
+/// before the first repeat.
+struct HaltCheck {
+    /// The address of the comparison
+    ip: usize,
+    /// The register that's compared against register 0
+    register: usize,
+}
+
+impl HaltCheck {
+    fn find(instructions: &[Instruction]) -> Self {
+        instructions
+            .iter()
+            .enumerate()
+            .filter(|(_, instr)| instr.opcode == Opcode::Eqrr)
+            .filter_map(|(ip, instr)| match (instr.in1, instr.in2) {
+                (0, 0) => None,
+                (0, register) | (register, 0) => Some(HaltCheck { ip, register }),
+                _ => None,
+            })
+            .next()
+            .expect("The program never compares anything against register 0")
+    }
+}
+
+/// Runs the program, yielding the value that register 0 is compared against each time the halting
+/// check is reached.  Stops once the program halts.
+struct HaltingValues {
+    vm: VM,
+    check: HaltCheck,
+}
+
+impl Iterator for HaltingValues {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            // Read the value before executing the check, which may itself halt the program
+            let checked = if self.vm.ip() == self.check.ip {
+                Some(self.vm.regs[self.check.register])
+            } else {
+                None
+            };
+            match self.vm.tick() {
+                Ok(false) => (),
+                Ok(true) => return None,
+                Err(err) => panic!("{} at instruction {}", err, self.vm.ip()),
+            }
+            if checked.is_some() {
+                return checked;
+            }
+            if self.vm.is_halted() {
+                return None;
+            }
+        }
+    }
+}
+
+fn parse_input(input: &str) -> VM {
+    let program: Program = input
+        .parse()
+        .unwrap_or_else(|err| panic!("Invalid program: {}", err));
+
+    let mut vm = VM::new(&program);
+    // The inner loop divides by 256 one step at a time
+    optimize(&mut vm.instructions, vm.regs.ip_register);
+    vm
+}
+
+fn halting_values(vm: &VM) -> HaltingValues {
+    HaltingValues {
+        vm: vm.clone(),
+        check: HaltCheck::find(&vm.instructions.instructions),
+    }
+}
+
+/// The fewest instructions are executed if register 0 matches the first value it's checked
+/// against.
+fn part1(vm: &VM) -> usize {
+    halting_values(vm)
+        .next()
+        .expect("The program halted without reaching the check")
+}
+
+/// The most instructions are executed if register 0 matches the last value it's checked against
+/// before the values start repeating.
+fn part2(vm: &VM) -> usize {
+    let mut seen = HashSet::new();
+    let mut last = None;
+
+    for value in halting_values(vm) {
+        if !seen.insert(value) {
+            break;
+        }
+        last = Some(value);
+    }
+
+    last.expect("The program halted without reaching the check")
+}
+
+pub struct Day21;
+
+impl Solution for Day21 {
+    type Input = VM;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> VM { parse_input(input) }
+
+    fn part1(vm: &VM) -> usize { part1(vm) }
+
+    fn part2(vm: &VM) -> usize { part2(vm) }
+}
+
+#[cfg(test)]
+fn synthetic_vm(program: &str, reg0: usize) -> VM {
+    let mut vm = VM::new(&program.parse().unwrap());
+    vm.regs[0] = reg0;
+    vm
+}
+
+#[test]
+fn halting_values_check_at_start() {
+    // The check is the very first instruction, so it must be read before the first tick
+    let mut vm = synthetic_vm("eqrr 1 0 2\naddi 1 1 1\n", 7);
+    vm.regs[1] = 42;
+    assert_eq!(halting_values(&vm).collect::<Vec<_>>(), [42]);
+    assert_eq!(part1(&vm), 42);
+    assert_eq!(part2(&vm), 42);
+}
+
+#[test]
+fn halting_values_loop() {
+    // e = 0; while e != a { e = (e + 3) & 7 }
+    let program = "#ip 5
+                   seti 0 0 1
+                   eqrr 1 0 2
+                   addr 2 5 5
+                   seti 4 0 5
+                   seti 99 0 5
+                   addi 1 3 1
+                   bani 1 7 1
+                   seti 0 0 5";
+
+    let vm = synthetic_vm(program, 100);
+    let values: Vec<_> = halting_values(&vm).take(9).collect();
+    assert_eq!(values, [0, 3, 6, 1, 4, 7, 2, 5, 0]);
+    assert_eq!(part1(&vm), 0);
+    assert_eq!(part2(&vm), 5);
+
+    // The value that halts the program is still reported
+    let vm = synthetic_vm(program, 6);
+    assert_eq!(halting_values(&vm).collect::<Vec<_>>(), [0, 3, 6]);
+}
+
+#[test]
+fn answers() {
+    let vm = parse_input(INPUT);
+    assert_eq!(part1(&vm), 10_720_163);
+    assert_eq!(part2(&vm), 5_885_821);
 }
@@ -7,6 +7,8 @@ use cached::UnboundCache;
 use pathfinding::prelude::*;
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref DEPTH_REGEX: Regex = Regex::new("depth: (\\d+)").unwrap();
     static ref TARGET_REGEX: Regex = Regex::new("target: (\\d+),(\\d+)").unwrap();
@@ -47,8 +49,8 @@ impl Region {
     }
 }
 
-fn parse_input() -> ((isize, isize), isize) {
-    let mut lines = INPUT.lines();
+fn parse_input(input: &str) -> ((isize, isize), isize) {
+    let mut lines = input.lines();
     let cap1 = DEPTH_REGEX.captures(lines.next().unwrap()).unwrap();
     let cap2 = TARGET_REGEX.captures(lines.next().unwrap()).unwrap();
 
@@ -72,8 +74,8 @@ fn get_geologic_index(x: isize, y: isize, target_x: isize, target_y: isize, dept
 }
 
 cached_key! {
-    EROSION: UnboundCache<(isize, isize), isize> = UnboundCache::new();
-    Key = { (x, y) };
+    EROSION: UnboundCache<(isize, isize, isize, isize, isize), isize> = UnboundCache::new();
+    Key = { (x, y, target_x, target_y, depth) };
 
     fn erosion_level(x: isize, y: isize, target_x: isize, target_y: isize, depth: isize) -> isize = {
         (get_geologic_index(x, y, target_x, target_y ,depth) + depth) % 20183
@@ -81,8 +83,8 @@ cached_key! {
 }
 
 cached_key! {
-    REGION: UnboundCache<(isize, isize), Region> = UnboundCache::new();
-    Key = { (x, y) };
+    REGION: UnboundCache<(isize, isize, isize, isize, isize), Region> = UnboundCache::new();
+    Key = { (x, y, target_x, target_y, depth) };
 
     fn get_region_type(x: isize, y: isize, target_x: isize, target_y: isize, depth: isize) -> Region = {
         match erosion_level(x, y, target_x, target_y, depth) % 3 {
@@ -106,10 +108,7 @@ fn get_risk_level(x: isize, y: isize, target_x: isize, target_y: isize, depth: i
     risk
 }
 
-fn part1() -> usize {
-    let ((x, y), depth) = parse_input();
-    get_risk_level(x, y, x, y, depth)
-}
+fn part1(&((x, y), depth): &((isize, isize), isize)) -> usize { get_risk_level(x, y, x, y, depth) }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Tool {
@@ -151,9 +150,7 @@ fn manhattan_distance(x1: isize, y1: isize, x2: isize, y2: isize) -> isize {
 #[derive(Clone, Copy, PartialEq, Eq)]
 struct Coord(isize, isize, Option<Tool>);
 
-pub fn part2() -> usize {
-    let ((target_x, target_y), depth) = parse_input();
-
+pub fn part2(&((target_x, target_y), depth): &((isize, isize), isize)) -> usize {
     astar(
         &(0, 0, Some(Tool::Torch)),
         |&(x, y, tool)| iter_neighbors(x, y, target_x, target_y, tool, depth),
@@ -169,10 +166,24 @@ mod test {
     extern crate test;
 
     #[bench]
-    fn bench_part2(b: &mut test::Bencher) { b.iter(super::part2) }
+    fn bench_part2(b: &mut test::Bencher) {
+        let cave = super::parse_input(super::INPUT);
+        b.iter(|| super::part2(&cave))
+    }
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day22;
+
+impl Solution for Day22 {
+    type Input = ((isize, isize), isize);
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> ((isize, isize), isize) { parse_input(input) }
+
+    fn part1(cave: &((isize, isize), isize)) -> usize { part1(cave) }
+
+    fn part2(cave: &((isize, isize), isize)) -> usize { part2(cave) }
 }
@@ -2,13 +2,15 @@ use std::usize;
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex = Regex::new("pos=<(-?\\d+),(-?\\d+),(-?\\d+)>, r=(\\d+)").unwrap();
 }
 
 const INPUT: &str = include_str!("../input/day23.txt");
 
-struct Nanobot {
+pub struct Nanobot {
     /// (X,Y,Z)
     pub pos: (isize, isize, isize),
     pub radius: isize,
@@ -28,18 +30,22 @@ impl Nanobot {
     }
 }
 
-fn parse_input() -> impl Iterator<Item = Nanobot> {
-    INPUT.lines().filter(|l| !l.is_empty()).map(|line| {
-        let caps = RGX.captures(line).unwrap();
-        Nanobot {
-            pos: (
-                caps[1].parse().unwrap(),
-                caps[2].parse().unwrap(),
-                caps[3].parse().unwrap(),
-            ),
-            radius: caps[4].parse().unwrap(),
-        }
-    })
+fn parse_input(input: &str) -> Vec<Nanobot> {
+    input
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let caps = RGX.captures(line).unwrap();
+            Nanobot {
+                pos: (
+                    caps[1].parse().unwrap(),
+                    caps[2].parse().unwrap(),
+                    caps[3].parse().unwrap(),
+                ),
+                radius: caps[4].parse().unwrap(),
+            }
+        })
+        .collect()
 }
 
 fn manhattan_distance(x1: isize, y1: isize, z1: isize, x2: isize, y2: isize, z2: isize) -> isize {
@@ -49,8 +55,7 @@ fn manhattan_distance(x1: isize, y1: isize, z1: isize, x2: isize, y2: isize, z2:
     x_diff + y_diff + z_diff
 }
 
-fn part1() -> usize {
-    let nanobots = parse_input().collect::<Vec<_>>();
+fn part1(nanobots: &[Nanobot]) -> usize {
     let strongest_nanobot = nanobots
         .iter()
         .max_by_key(|&Nanobot { radius, .. }| radius)
@@ -73,8 +78,7 @@ fn part1() -> usize {
 }
 
 #[allow(clippy::let_and_return)]
-fn part2() -> i64 {
-    let nanobots = parse_input().collect::<Vec<_>>();
+fn part2(nanobots: &[Nanobot]) -> i64 {
     let (min_x, max_x, min_y, max_y, min_z, max_z) = nanobots.iter().fold(
         (
             isize::max_value(),
@@ -144,7 +148,18 @@ fn part2() -> i64 {
     res
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day23;
+
+impl Solution for Day23 {
+    type Input = Vec<Nanobot>;
+    type Part1 = usize;
+    type Part2 = i64;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Nanobot> { parse_input(input) }
+
+    fn part1(nanobots: &Vec<Nanobot>) -> usize { part1(nanobots) }
+
+    fn part2(nanobots: &Vec<Nanobot>) -> i64 { part2(nanobots) }
 }
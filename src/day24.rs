@@ -2,6 +2,8 @@ use std::{cmp::Reverse, str::FromStr};
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref WEAK_TO_RGX: Regex = Regex::new(r"weak to ((?:\w+,? ?)*)").unwrap();
     static ref IMMUNE_TO_RGX: Regex = Regex::new(r"immune to ((?:\w+,? ?)*)").unwrap();
@@ -11,7 +13,7 @@ lazy_static! {
 const INPUT: &str = include_str!("../input/day24.txt");
 
 #[derive(Clone, Copy, PartialEq, Debug)]
-enum DamageType {
+pub enum DamageType {
     Bludgeoning,
     Slashing,
     Fire,
@@ -36,7 +38,7 @@ impl FromStr for DamageType {
 }
 
 #[derive(Clone, PartialEq, Debug)]
-struct UnitGroup {
+pub struct UnitGroup {
     pub number: usize,
     pub hp: usize,
     pub weak_to: Vec<DamageType>,
@@ -111,15 +113,15 @@ impl FromStr for UnitGroup {
     }
 }
 
-fn parse_input() -> (Vec<UnitGroup>, Vec<UnitGroup>) {
-    let immune_system_units = INPUT
+fn parse_input(input: &str) -> (Vec<UnitGroup>, Vec<UnitGroup>) {
+    let immune_system_units = input
         .lines()
         .skip(1)
         .take_while(|l| !l.is_empty())
         .map(UnitGroup::from_str)
         .map(Result::unwrap)
         .collect::<Vec<_>>();
-    let infection_units = INPUT
+    let infection_units = input
         .lines()
         .skip_while(|&l| l != "Infection:")
         .skip(1)
@@ -240,8 +242,8 @@ fn count_remaining_units(groups: &[UnitGroup]) -> usize {
     groups.iter().map(|group| group.number).sum()
 }
 
-fn part1() -> usize {
-    let (mut immune_system_units, mut infection_units) = parse_input();
+fn part1(armies: &(Vec<UnitGroup>, Vec<UnitGroup>)) -> usize {
+    let (mut immune_system_units, mut infection_units) = armies.clone();
 
     simulate(&mut immune_system_units, &mut infection_units);
 
@@ -253,8 +255,7 @@ fn part1() -> usize {
     count_remaining_units(&winning_army)
 }
 
-fn part2() -> usize {
-    let original_inputs = parse_input();
+fn part2(original_inputs: &(Vec<UnitGroup>, Vec<UnitGroup>)) -> usize {
     let mut boost = 0;
     loop {
         boost += 1;
@@ -272,7 +273,18 @@ fn part2() -> usize {
     }
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day24;
+
+impl Solution for Day24 {
+    type Input = (Vec<UnitGroup>, Vec<UnitGroup>);
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> (Vec<UnitGroup>, Vec<UnitGroup>) { parse_input(input) }
+
+    fn part1(armies: &(Vec<UnitGroup>, Vec<UnitGroup>)) -> usize { part1(armies) }
+
+    fn part2(armies: &(Vec<UnitGroup>, Vec<UnitGroup>)) -> usize { part2(armies) }
 }
@@ -2,6 +2,8 @@ use std::collections::VecDeque;
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex = Regex::new("\\w*(-?\\d+),(-?\\d+),(-?\\d+),(-?\\d+)").unwrap();
 }
@@ -19,21 +21,27 @@ fn manhattan_distance(
     x_diff + y_diff + z_diff + t_diff
 }
 
-fn parse_input() -> impl Iterator<Item = (isize, isize, isize, isize)> {
-    INPUT.lines().filter(|l| !l.is_empty()).map(|l| {
-        let caps = RGX.captures(l).unwrap();
-        (
-            caps[1].parse().unwrap(),
-            caps[2].parse().unwrap(),
-            caps[3].parse().unwrap(),
-            caps[4].parse().unwrap(),
-        )
-    })
+type Star = (isize, isize, isize, isize);
+
+fn parse_input(input: &str) -> Vec<Star> {
+    input
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            let caps = RGX.captures(l).unwrap();
+            (
+                caps[1].parse().unwrap(),
+                caps[2].parse().unwrap(),
+                caps[3].parse().unwrap(),
+                caps[4].parse().unwrap(),
+            )
+        })
+        .collect()
 }
 
-fn part1() -> usize {
+fn part1(stars: &[Star]) -> usize {
     let mut constellations: VecDeque<Vec<(isize, isize, isize, isize)>> = VecDeque::new();
-    for new_star in parse_input() {
+    for &new_star in stars {
         let mut match_found = false;
         for constellation in &mut constellations {
             let mut connects = false;
@@ -97,9 +105,20 @@ fn part1() -> usize {
     }
 }
 
-fn part2() -> usize { 0 }
+fn part2(_stars: &[Star]) -> usize { 0 }
+
+pub struct Day25;
+
+impl Solution for Day25 {
+    type Input = Vec<Star>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Star> { parse_input(input) }
+
+    fn part1(stars: &Vec<Star>) -> usize { part1(stars) }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+    fn part2(stars: &Vec<Star>) -> usize { part2(stars) }
 }
@@ -2,6 +2,8 @@ use std::collections::HashSet;
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex = Regex::new("#(\\d+) @ (\\d+),(\\d+): (\\d+)x(\\d+)").unwrap();
 }
@@ -9,7 +11,7 @@ lazy_static! {
 const INPUT: &str = include_str!("../input/day3.txt");
 
 #[derive(Default, Debug)]
-struct Claim {
+pub struct Claim {
     pub id: usize,
     pub from_left: usize,
     pub from_top: usize,
@@ -17,23 +19,25 @@ struct Claim {
     pub height: usize,
 }
 
-fn parse_input() -> impl Iterator<Item = Claim> {
-    RGX.captures_iter(INPUT).map(|cap| Claim {
-        id: cap[1].parse().unwrap(),
-        from_left: cap[2].parse().unwrap(),
-        from_top: cap[3].parse().unwrap(),
-        width: cap[4].parse().unwrap(),
-        height: cap[5].parse().unwrap(),
-    })
+fn parse_input(input: &str) -> Vec<Claim> {
+    RGX.captures_iter(input)
+        .map(|cap| Claim {
+            id: cap[1].parse().unwrap(),
+            from_left: cap[2].parse().unwrap(),
+            from_top: cap[3].parse().unwrap(),
+            width: cap[4].parse().unwrap(),
+            height: cap[5].parse().unwrap(),
+        })
+        .collect()
 }
 
 #[derive(Clone, Default)]
 struct FabricSquare(HashSet<usize>);
 
-fn build_fabric() -> Vec<Vec<FabricSquare>> {
+fn build_fabric(claims: &[Claim]) -> Vec<Vec<FabricSquare>> {
     let mut fabric: Vec<Vec<FabricSquare>> = vec![vec![FabricSquare::default(); 1000]; 1000];
 
-    for claim in parse_input() {
+    for claim in claims {
         for row in &mut fabric[claim.from_top..(claim.from_top + claim.height)] {
             for square in &mut row[claim.from_left..(claim.from_left + claim.width)] {
                 square.0.insert(claim.id);
@@ -44,17 +48,17 @@ fn build_fabric() -> Vec<Vec<FabricSquare>> {
     fabric
 }
 
-fn part1() -> usize {
-    build_fabric()
+fn part1(claims: &[Claim]) -> usize {
+    build_fabric(claims)
         .into_iter()
         .flat_map(|v| v.into_iter())
         .fold(0, |acc, v| if v.0.len() >= 2 { acc + 1 } else { acc })
 }
 
-fn part2() -> usize {
+fn part2(claims: &[Claim]) -> usize {
     let mut invalid_claims: HashSet<usize> = HashSet::with_capacity(1300);
 
-    build_fabric()
+    build_fabric(claims)
         .into_iter()
         .flat_map(|v| v.into_iter())
         .for_each(|mut square| {
@@ -65,7 +69,8 @@ fn part2() -> usize {
             }
         });
 
-    for i in 1..=1295 {
+    let max_claim_id = claims.iter().map(|claim| claim.id).max().unwrap_or(0);
+    for i in 1..=max_claim_id {
         if invalid_claims.get(&i).is_none() {
             return i;
         }
@@ -74,7 +79,18 @@ fn part2() -> usize {
     unreachable!();
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day3;
+
+impl Solution for Day3 {
+    type Input = Vec<Claim>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Claim> { parse_input(input) }
+
+    fn part1(claims: &Vec<Claim>) -> usize { part1(claims) }
+
+    fn part2(claims: &Vec<Claim>) -> usize { part2(claims) }
 }
@@ -4,6 +4,8 @@ use std::cmp::Ordering;
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex =
         Regex::new("\\[(\\d+)-(\\d+)-(\\d+) (\\d\\d):(\\d\\d)\\] (.*)").unwrap();
@@ -13,7 +15,7 @@ lazy_static! {
 const INPUT: &str = include_str!("../input/day4.txt");
 
 #[derive(Debug, PartialEq, Eq)]
-enum Action {
+pub enum Action {
     StartShift(usize),
     FallAsleep,
     WakeUp,
@@ -29,7 +31,7 @@ impl Action {
 }
 
 #[derive(Debug, PartialEq, Eq)]
-struct Event {
+pub struct Event {
     pub year: usize,
     pub month: usize,
     pub day: usize,
@@ -67,9 +69,9 @@ fn parse_action(action: &str) -> Action {
     }
 }
 
-fn parse_input() -> impl Iterator<Item = Event> {
+fn parse_input(input: &str) -> Vec<Event> {
     let mut events: Vec<Event> = RGX
-        .captures_iter(INPUT)
+        .captures_iter(input)
         .map(|cap| Event {
             year: cap[1].parse().unwrap(),
             month: cap[2].parse().unwrap(),
@@ -81,13 +83,13 @@ fn parse_input() -> impl Iterator<Item = Event> {
         .collect();
 
     events.sort_unstable();
-    events.into_iter()
+    events
 }
 
-fn get_guard_sleep_totals() -> Vec<[usize; 60]> {
+fn get_guard_sleep_totals(events: &[Event]) -> Vec<[usize; 60]> {
     let mut guard_sleep_totals: Vec<[usize; 60]> = vec![[0; 60]; 4000];
 
-    let mut input_iter = parse_input();
+    let mut input_iter = events.iter();
     let first_evt = input_iter.next().unwrap();
     let first_guard_id = first_evt.action.guard_id().unwrap();
     input_iter.fold(
@@ -128,8 +130,8 @@ fn find_max_ix_by<T, B: PartialEq + Ord>(
         })
 }
 
-fn part1() -> usize {
-    let guard_sleep_totals: Vec<[usize; 60]> = get_guard_sleep_totals();
+fn part1(events: &[Event]) -> usize {
+    let guard_sleep_totals: Vec<[usize; 60]> = get_guard_sleep_totals(events);
 
     let (best_guard_id, _): (usize, usize) = find_max_ix_by(
         |sleep_times| sleep_times.iter().sum(),
@@ -143,8 +145,8 @@ fn part1() -> usize {
     best_guard_id * best_minute
 }
 
-pub fn part2() -> impl ::std::fmt::Display {
-    let guard_sleep_totals: Vec<[usize; 60]> = get_guard_sleep_totals();
+pub fn part2(events: &[Event]) -> usize {
+    let guard_sleep_totals: Vec<[usize; 60]> = get_guard_sleep_totals(events);
 
     let (most_slept_minute_guard_id, (_, most_slept_minute)) = find_max_ix_by(
         |sleep_times| -> (usize, usize) {
@@ -165,10 +167,24 @@ mod test {
     extern crate test;
 
     #[bench]
-    fn bench_part_2(b: &mut test::Bencher) { b.iter(super::part2) }
+    fn bench_part_2(b: &mut test::Bencher) {
+        let events = super::parse_input(super::INPUT);
+        b.iter(|| super::part2(&events))
+    }
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day4;
+
+impl Solution for Day4 {
+    type Input = Vec<Event>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Event> { parse_input(input) }
+
+    fn part1(events: &Vec<Event>) -> usize { part1(events) }
+
+    fn part2(events: &Vec<Event>) -> usize { part2(events) }
 }
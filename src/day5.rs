@@ -2,16 +2,18 @@ extern crate rayon;
 
 use rayon::{iter::IntoParallelIterator, prelude::*};
 
+use crate::solution::Solution;
+
 const INPUT: &str = include_str!("../input/day5.txt");
 
-fn parse_input() -> impl Iterator<Item = char> { INPUT.chars() }
+fn parse_input(input: &str) -> String { input.trim().to_owned() }
 
 fn needs_delete(c1: char, c2: char) -> bool {
     c1 != c2 && c1.to_ascii_uppercase() == c2.to_ascii_uppercase()
 }
 
-fn react_polymer(polymer: impl Iterator<Item = char>) -> usize {
-    let mut acc = String::with_capacity(INPUT.len());
+fn react_polymer(polymer: impl Iterator<Item = char>, capacity: usize) -> usize {
+    let mut acc = String::with_capacity(capacity);
     acc.push('*');
     acc.push('*');
 
@@ -35,12 +37,12 @@ fn react_polymer(polymer: impl Iterator<Item = char>) -> usize {
         acc
     });
 
-    reacted_polymer.len() - 3
+    reacted_polymer.len() - 2
 }
 
-fn part1() -> usize { react_polymer(parse_input()) }
+fn part1(polymer: &str) -> usize { react_polymer(polymer.chars(), polymer.len()) }
 
-pub fn part2() -> usize {
+pub fn part2(polymer: &str) -> usize {
     // Props to https://github.com/CryZe for coming up with the Rayon idea
     // Can't use an inclusive range here because `IntoParallelIterator` isn't impelemented for it
     // upstream
@@ -49,7 +51,10 @@ pub fn part2() -> usize {
         .into_par_iter()
         .map(|c| -> usize {
             let c = c as u8 as char;
-            react_polymer(parse_input().filter(|c2| c2.to_ascii_uppercase() != c))
+            react_polymer(
+                polymer.chars().filter(|c2| c2.to_ascii_uppercase() != c),
+                polymer.len(),
+            )
         })
         .reduce_with(|a, b| a.min(b))
         .unwrap()
@@ -60,10 +65,24 @@ mod test {
     extern crate test;
 
     #[bench]
-    fn bench_p2(b: &mut test::Bencher) { b.iter(super::part2) }
+    fn bench_p2(b: &mut test::Bencher) {
+        let polymer = super::parse_input(super::INPUT);
+        b.iter(|| super::part2(&polymer))
+    }
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day5;
+
+impl Solution for Day5 {
+    type Input = String;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> String { parse_input(input) }
+
+    fn part1(polymer: &String) -> usize { part1(polymer) }
+
+    fn part2(polymer: &String) -> usize { part2(polymer) }
 }
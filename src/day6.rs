@@ -2,15 +2,18 @@ use std::{collections::HashSet, usize};
 
 use regex::Regex;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex = Regex::new("(\\d+), (\\d+)").unwrap();
 }
 
 const INPUT: &str = include_str!("../input/day6.txt");
 
-fn parse_input() -> impl Iterator<Item = (usize, usize)> {
-    RGX.captures_iter(INPUT)
+fn parse_input(input: &str) -> Vec<(usize, usize)> {
+    RGX.captures_iter(input)
         .map(|cap| (cap[1].parse().unwrap(), cap[2].parse().unwrap()))
+        .collect()
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -25,8 +28,7 @@ fn manhattan_distance(x1: usize, y1: usize, x2: usize, y2: usize) -> usize {
     x_diff + y_diff
 }
 
-fn part1() -> usize {
-    let input: Vec<(usize, usize)> = parse_input().collect();
+fn part1(input: &[(usize, usize)]) -> usize {
     let (max_x, max_y) = input.iter().fold(
         (usize::min_value(), usize::min_value()),
         |(max_x, max_y), (x, y)| (max_x.max(*x), max_y.max(*y)),
@@ -82,8 +84,7 @@ fn part1() -> usize {
         .unwrap()
 }
 
-fn part2() -> usize {
-    let input: Vec<(usize, usize)> = parse_input().collect();
+fn part2(input: &[(usize, usize)]) -> usize {
     let (max_x, max_y) = input.iter().fold(
         (usize::min_value(), usize::min_value()),
         |(max_x, max_y), (x, y)| (max_x.max(*x), max_y.max(*y)),
@@ -105,7 +106,18 @@ fn part2() -> usize {
     count
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day6;
+
+impl Solution for Day6 {
+    type Input = Vec<(usize, usize)>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<(usize, usize)> { parse_input(input) }
+
+    fn part1(input: &Vec<(usize, usize)>) -> usize { part1(input) }
+
+    fn part2(input: &Vec<(usize, usize)>) -> usize { part2(input) }
 }
@@ -6,6 +6,8 @@ use std::{
 use regex::Regex;
 use slab::Slab;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex =
         Regex::new("Step (.) must be finished before step (.) can begin\\.").unwrap();
@@ -13,9 +15,10 @@ lazy_static! {
 
 const INPUT: &str = include_str!("../input/day7.txt");
 
-fn parse_input() -> impl Iterator<Item = (char, char)> {
-    RGX.captures_iter(INPUT)
+fn parse_input(input: &str) -> Vec<(char, char)> {
+    RGX.captures_iter(input)
         .map(|cap| (cap[1].parse().unwrap(), cap[2].parse().unwrap()))
+        .collect()
 }
 
 #[derive(Debug)]
@@ -25,7 +28,9 @@ impl DagNode {
     pub fn new(c: char) -> Self { DagNode(Vec::new(), c) }
 }
 
-fn init_nodes() -> (
+fn init_nodes(
+    steps: &[(char, char)],
+) -> (
     Slab<DagNode>,
     HashMap<char, usize>,
     HashMap<char, usize>,
@@ -36,7 +41,7 @@ fn init_nodes() -> (
     let mut key_mappings: HashMap<char, usize> = HashMap::new();
     let mut linked_to = HashSet::new(); // used to find the head
 
-    for (from, to) in parse_input() {
+    for &(from, to) in steps {
         *prereq_counts.entry(to).or_insert(0) += 1;
         linked_to.insert(to);
         let from_key = *key_mappings
@@ -71,8 +76,8 @@ fn init_active_nodes<'a>(
     active_nodes
 }
 
-fn part1() -> String {
-    let (nodes, mut prereq_counts, key_mappings, linked_to) = init_nodes();
+fn part1(steps: &[(char, char)]) -> String {
+    let (nodes, mut prereq_counts, key_mappings, linked_to) = init_nodes(steps);
     let mut active_nodes = init_active_nodes(&nodes, &mut prereq_counts, &key_mappings, &linked_to);
 
     let mut ordered = String::new();
@@ -99,8 +104,8 @@ fn part1() -> String {
 
 fn dur(c: char) -> usize { ((c as u8) - 64) as usize + 60 }
 
-fn part2() -> usize {
-    let (nodes, mut prereq_counts, key_mappings, linked_to) = init_nodes();
+fn part2(steps: &[(char, char)]) -> usize {
+    let (nodes, mut prereq_counts, key_mappings, linked_to) = init_nodes(steps);
     let mut active_nodes = init_active_nodes(&nodes, &mut prereq_counts, &key_mappings, &linked_to);
 
     let mut time = 0;
@@ -146,7 +151,18 @@ fn part2() -> usize {
     time - 1
 }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+pub struct Day7;
+
+impl Solution for Day7 {
+    type Input = Vec<(char, char)>;
+    type Part1 = String;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<(char, char)> { parse_input(input) }
+
+    fn part1(steps: &Vec<(char, char)>) -> String { part1(steps) }
+
+    fn part2(steps: &Vec<(char, char)>) -> usize { part2(steps) }
 }
@@ -1,7 +1,9 @@
+use crate::solution::Solution;
+
 const INPUT: &str = include_str!("../input/day8.txt");
 
 #[derive(Default, Debug)]
-struct Node {
+pub struct Node {
     pub children: Vec<Node>,
     pub metadata: Vec<usize>,
 }
@@ -43,8 +45,8 @@ fn parse_node(data: &mut Vec<usize>) -> Node {
     item
 }
 
-fn parse_input() -> impl Iterator<Item = Node> {
-    let mut data: Vec<usize> = INPUT
+fn parse_input(input: &str) -> Vec<Node> {
+    let mut data: Vec<usize> = input
         .split_whitespace()
         .map(|n| -> usize { n.parse().unwrap() })
         .collect();
@@ -55,14 +57,25 @@ fn parse_input() -> impl Iterator<Item = Node> {
         items.push(parse_node(&mut data));
     }
 
-    items.into_iter()
+    items
 }
 
-fn part1() -> usize { parse_input().map(|node| node.sum_metadata()).sum() }
+fn part1(nodes: &[Node]) -> usize { nodes.iter().map(|node| node.sum_metadata()).sum() }
+
+fn part2(nodes: &[Node]) -> usize { nodes[0].value() }
+
+pub struct Day8;
+
+impl Solution for Day8 {
+    type Input = Vec<Node>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> Vec<Node> { parse_input(input) }
 
-fn part2() -> usize { parse_input().next().unwrap().value() }
+    fn part1(nodes: &Vec<Node>) -> usize { part1(nodes) }
 
-pub fn run() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+    fn part2(nodes: &Vec<Node>) -> usize { part2(nodes) }
 }
@@ -1,6 +1,8 @@
 use regex::Regex;
 use slab::Slab;
 
+use crate::solution::Solution;
+
 lazy_static! {
     static ref RGX: Regex =
         Regex::new("(\\d+) players; last marble is worth (\\d+) points").unwrap();
@@ -8,8 +10,8 @@ lazy_static! {
 
 const INPUT: &str = include_str!("../input/day9.txt");
 
-fn parse_input() -> (usize, usize) {
-    let captures = RGX.captures(INPUT).unwrap();
+fn parse_input(input: &str) -> (usize, usize) {
+    let captures = RGX.captures(input).unwrap();
     (captures[1].parse().unwrap(), captures[2].parse().unwrap())
 }
 
@@ -19,8 +21,7 @@ struct Node {
     pub next: usize,
 }
 
-fn solve() -> (usize, usize) {
-    let (players, last_marble_value) = parse_input();
+fn solve(players: usize, last_marble_value: usize) -> usize {
     let mut board: Slab<Node> = Slab::new();
 
     let mut i = 0;
@@ -52,7 +53,6 @@ fn solve() -> (usize, usize) {
         board[old_next].prev = new_next;
     };
 
-    let mut part1 = 0;
     loop {
         for player in 0..players {
             i += 1;
@@ -73,16 +73,32 @@ fn solve() -> (usize, usize) {
             cur_marble_key = board[cur_marble_key].next;
 
             if i == last_marble_value {
-                part1 = *player_scores.iter().max().unwrap();
-            } else if i == last_marble_value * 100 {
-                return (part1, player_scores.into_iter().max().unwrap());
+                return player_scores.into_iter().max().unwrap();
             }
         }
     }
 }
 
-pub fn run() {
-    let (part1, part2) = solve();
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+fn part1(&(players, last_marble_value): &(usize, usize)) -> usize {
+    solve(players, last_marble_value)
+}
+
+fn part2(&(players, last_marble_value): &(usize, usize)) -> usize {
+    solve(players, last_marble_value * 100)
+}
+
+pub struct Day9;
+
+impl Solution for Day9 {
+    type Input = (usize, usize);
+    type Part1 = usize;
+    type Part2 = usize;
+
+    const INPUT: &'static str = INPUT;
+
+    fn parse(input: &str) -> (usize, usize) { parse_input(input) }
+
+    fn part1(game: &(usize, usize)) -> usize { part1(game) }
+
+    fn part2(game: &(usize, usize)) -> usize { part2(game) }
 }
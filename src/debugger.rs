@@ -0,0 +1,362 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, BufRead, Write},
+};
+
+use crate::asm_common::*;
+
+/// Why the debugger handed control back to the user
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum StopReason {
+    /// The requested number of instructions were executed.
+    Stepped,
+    /// The next instruction to be executed has a breakpoint on it.
+    Breakpoint(usize),
+    /// The last executed instruction changed the value of a watched register.
+    Watchpoint {
+        register: usize,
+        old: usize,
+        new: usize,
+    },
+    /// The instruction pointer left the program.
+    Halted,
+    /// The next instruction to be executed failed, so it wasn't.
+    Fault(VmError),
+}
+
+/// How many instructions can be stepped back through
+const HISTORY_CAPACITY: usize = 10_000;
+
+/// Wraps a VM, executing it one instruction at a time while checking breakpoints and watchpoints.
+pub struct Debugger {
+    pub vm: VM,
+    pub breakpoints: BTreeSet<usize>,
+    pub watchpoints: BTreeSet<usize>,
+    /// The number of instructions executed so far
+    pub executed: usize,
+}
+
+impl Debugger {
+    /// Wraps `vm`, enabling profiling and history for it.
+    pub fn new(mut vm: VM) -> Self {
+        if vm.profile.is_none() {
+            vm.enable_profiling();
+        }
+        if vm.history.is_none() {
+            vm.enable_history(HISTORY_CAPACITY);
+        }
+
+        Debugger {
+            vm,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            executed: 0,
+        }
+    }
+
+    pub fn is_halted(&self) -> bool { self.vm.is_halted() }
+
+    /// Executes a single instruction, returning the first watched register that it changed.
+    fn tick(&mut self) -> Option<StopReason> {
+        let before = self.vm.regs.regs;
+        match self.vm.tick() {
+            Ok(false) => (),
+            Ok(true) => return Some(StopReason::Halted),
+            Err(err) => return Some(StopReason::Fault(err)),
+        }
+        self.executed += 1;
+
+        self.watchpoints
+            .iter()
+            .find(|&&reg| before[reg] != self.vm.regs[reg])
+            .map(|&register| StopReason::Watchpoint {
+                register,
+                old: before[register],
+                new: self.vm.regs[register],
+            })
+    }
+
+    /// Executes up to `count` instructions, stopping early if a watchpoint triggers, an instruction
+    /// fails or the program halts.  Breakpoints are ignored.
+    pub fn step(&mut self, count: usize) -> StopReason {
+        for _ in 0..count {
+            if let Some(reason) = self.tick() {
+                return reason;
+            }
+        }
+
+        if self.is_halted() {
+            StopReason::Halted
+        } else {
+            StopReason::Stepped
+        }
+    }
+
+    /// Undoes up to `count` instructions, returning how many were actually undone.
+    pub fn step_back(&mut self, count: usize) -> usize {
+        let undone = (0..count).take_while(|_| self.vm.step_back()).count();
+        self.executed -= undone;
+        undone
+    }
+
+    /// Runs until the program halts, a watchpoint triggers, an instruction fails or the next
+    /// instruction to be executed has a breakpoint on it.  Always executes at least one instruction
+    /// so that continuing from a breakpoint makes progress.
+    pub fn cont(&mut self) -> StopReason {
+        loop {
+            if let Some(reason) = self.tick() {
+                return reason;
+            }
+
+            let ip = self.vm.ip();
+            if self.breakpoints.contains(&ip) {
+                return StopReason::Breakpoint(ip);
+            }
+        }
+    }
+
+    /// Writes the instructions within `context` lines of the instruction pointer, marking the
+    /// current one with `=>` and ones with breakpoints with `*`.
+    fn list(&self, out: &mut impl Write, context: usize) -> io::Result<()> {
+        let ip = self.vm.ip();
+        let instructions = &self.vm.instructions.instructions;
+        let start = ip.saturating_sub(context);
+        let end = (ip + context + 1).min(instructions.len());
+
+        for (i, instr) in instructions.iter().enumerate().take(end).skip(start) {
+            let marker = if i == ip { "=>" } else { "  " };
+            let breakpoint = if self.breakpoints.contains(&i) {
+                '*'
+            } else {
+                ' '
+            };
+            writeln!(out, "{}{} {:>3}: {}", marker, breakpoint, i, instr)?;
+        }
+
+        Ok(())
+    }
+
+    fn report_stop(&self, out: &mut impl Write, reason: StopReason) -> io::Result<()> {
+        match reason {
+            StopReason::Stepped => (),
+            StopReason::Breakpoint(ip) => writeln!(out, "Breakpoint at {}", ip)?,
+            StopReason::Watchpoint { register, old, new } =>
+                writeln!(out, "Register {} changed: {} -> {}", register, old, new)?,
+            StopReason::Halted =>
+                writeln!(out, "Program halted after {} instructions", self.executed)?,
+            StopReason::Fault(err) => writeln!(out, "{}", err)?,
+        }
+
+        writeln!(out, "ip={} {}", self.vm.ip(), self.vm.regs)?;
+        if !self.is_halted() {
+            self.list(out, 0)?;
+        }
+
+        Ok(())
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  s, step [n]          execute the next n (default 1) instructions
+  bs, back [n]         undo the last n (default 1) instructions
+  c, continue          run until a breakpoint, watchpoint or the program halts
+  b, break <ip>        set a breakpoint on an instruction
+  d, delete <ip>       remove a breakpoint
+  w, watch <reg>       stop whenever a register changes
+  u, unwatch <reg>     remove a watchpoint
+  r, regs              print the registers
+  set <reg> <value>    change the value of a register
+  l, list [n]          print the instructions within n (default 5) of the current one
+  p, profile [n]       print the n (default 10) most executed instructions and loop back-edges
+  save <path>          write the registers, instruction pointer and program to a file
+  load <path>          replace the program and its state with one saved to a file
+  h, help              print this message
+  q, quit              exit the debugger
+An empty line repeats the last command.";
+
+fn parse_arg(arg: Option<&str>, max: Option<usize>) -> Result<usize, String> {
+    let arg = arg.ok_or_else(|| "Missing argument".to_owned())?;
+    let n = arg
+        .parse()
+        .map_err(|_| format!("Expected a number, found `{}`", arg))?;
+
+    match max {
+        Some(max) if n >= max => Err(format!("{} is out of range; must be less than {}", n, max)),
+        _ => Ok(n),
+    }
+}
+
+/// Runs a single command.  Returns `Ok(false)` if the debugger should exit.
+fn exec_command(dbg: &mut Debugger, out: &mut impl Write, line: &str) -> Result<bool, String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some(command) => command,
+        None => return Ok(true),
+    };
+    let arg = words.next();
+    let instruction_count = Some(dbg.vm.instructions.instructions.len());
+    let io_err = |err: io::Error| err.to_string();
+
+    match command {
+        "s" | "step" => {
+            let count = arg.map_or(Ok(1), |_| parse_arg(arg, None))?;
+            let reason = dbg.step(count);
+            dbg.report_stop(out, reason).map_err(io_err)?;
+        },
+        "bs" | "back" => {
+            let count = arg.map_or(Ok(1), |_| parse_arg(arg, None))?;
+            let undone = dbg.step_back(count);
+            if undone < count {
+                writeln!(out, "Stepped back {} instructions; no history left", undone)
+                    .map_err(io_err)?;
+            }
+            dbg.report_stop(out, StopReason::Stepped).map_err(io_err)?;
+        },
+        "c" | "continue" => {
+            if dbg.is_halted() {
+                return Err("The program has halted".to_owned());
+            }
+            let reason = dbg.cont();
+            dbg.report_stop(out, reason).map_err(io_err)?;
+        },
+        "b" | "break" => {
+            dbg.breakpoints.insert(parse_arg(arg, instruction_count)?);
+        },
+        "d" | "delete" => {
+            dbg.breakpoints.remove(&parse_arg(arg, instruction_count)?);
+        },
+        "w" | "watch" => {
+            dbg.watchpoints
+                .insert(parse_arg(arg, Some(dbg.vm.regs.count()))?);
+        },
+        "u" | "unwatch" => {
+            dbg.watchpoints
+                .remove(&parse_arg(arg, Some(dbg.vm.regs.count()))?);
+        },
+        "r" | "regs" => writeln!(out, "ip={} {}", dbg.vm.ip(), dbg.vm.regs).map_err(io_err)?,
+        "set" => {
+            let register = parse_arg(arg, Some(dbg.vm.regs.count()))?;
+            dbg.vm.regs[register] = parse_arg(words.next(), None)?;
+        },
+        "l" | "list" => {
+            let context = arg.map_or(Ok(5), |_| parse_arg(arg, None))?;
+            dbg.list(out, context).map_err(io_err)?;
+        },
+        "p" | "profile" => {
+            let top = arg.map_or(Ok(10), |_| parse_arg(arg, None))?;
+            let profile = dbg.vm.profile.as_ref().unwrap();
+            write!(
+                out,
+                "{}",
+                profile.report(&dbg.vm.instructions.instructions, top)
+            )
+            .map_err(io_err)?;
+        },
+        "save" => {
+            let path = arg.ok_or_else(|| "Missing path".to_owned())?;
+            let snapshot = dbg.vm.snapshot().map_err(|err| err.to_string())?;
+            fs::write(path, snapshot.to_string()).map_err(io_err)?;
+        },
+        "load" => {
+            let path = arg.ok_or_else(|| "Missing path".to_owned())?;
+            let snapshot: Snapshot = fs::read_to_string(path)
+                .map_err(io_err)?
+                .parse()
+                .map_err(|err| format!("Invalid snapshot: {}", err))?;
+            let vm = VM::restore(&snapshot).map_err(|err| err.to_string())?;
+            *dbg = Debugger {
+                breakpoints: dbg.breakpoints.clone(),
+                watchpoints: dbg.watchpoints.clone(),
+                ..Debugger::new(vm)
+            };
+            dbg.report_stop(out, StopReason::Stepped).map_err(io_err)?;
+        },
+        "h" | "help" => writeln!(out, "{}", HELP).map_err(io_err)?,
+        "q" | "quit" => return Ok(false),
+        _ => return Err(format!("Unknown command `{}`; try `help`", command)),
+    }
+
+    Ok(true)
+}
+
+/// Reads debugger commands from `input` until it runs out or the user quits, writing all output
+/// to `out`.
+pub fn run(vm: VM, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+    let mut dbg = Debugger::new(vm);
+    let mut last_command = String::new();
+
+    writeln!(out, "Type `help` for a list of commands.")?;
+    dbg.report_stop(&mut out, StopReason::Stepped)?;
+
+    let mut lines = input.lines();
+    loop {
+        write!(out, "(dbg) ")?;
+        out.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        if !line.trim().is_empty() {
+            last_command = line;
+        }
+
+        match exec_command(&mut dbg, &mut out, &last_command) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(msg) => writeln!(out, "{}", msg)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn breakpoints_and_watchpoints() {
+    // Counts register 1 up to 3, then halts
+    let program: Program = "#ip 0\nseti 0 0 1\naddi 1 1 1\ngtri 1 2 2\naddr 0 2 0\nseti 0 0 0\n"
+        .parse()
+        .unwrap();
+    let mut dbg = Debugger::new(VM::new(&program));
+
+    dbg.breakpoints.insert(3);
+    assert_eq!(dbg.cont(), StopReason::Breakpoint(3));
+    assert_eq!(dbg.vm.regs[1], 1);
+
+    dbg.breakpoints.clear();
+    dbg.watchpoints.insert(1);
+    assert_eq!(dbg.cont(), StopReason::Watchpoint {
+        register: 1,
+        old: 1,
+        new: 2,
+    });
+
+    assert_eq!(dbg.step(2), StopReason::Stepped);
+    dbg.watchpoints.clear();
+    assert_eq!(dbg.cont(), StopReason::Halted);
+    assert_eq!(dbg.vm.regs[1], 3);
+    assert_eq!(dbg.executed, 12);
+
+    // Back to just before the last increment
+    assert_eq!(dbg.step_back(3), 3);
+    assert_eq!((dbg.vm.ip(), dbg.vm.regs[1]), (1, 2));
+    assert_eq!(dbg.executed, 9);
+    assert_eq!(dbg.step_back(100), 9);
+    assert_eq!(dbg.vm.regs.regs, [0; REGISTER_COUNT]);
+}
+
+#[test]
+fn scripted_session() {
+    let program: Program = "seti 5 0 1\naddi 1 1 1\n".parse().unwrap();
+    let commands = "b 1\nc\nset 1 10\n\nr\nstep 2\nfoo\n";
+    let mut out = Vec::new();
+    run(VM::new(&program), commands.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains("Breakpoint at 1\nip=1 [0 5 0 0 0 0]\n=>*   1: addi 1 1 1\n"));
+    assert!(out.contains("ip=1 [0 10 0 0 0 0]\n"));
+    assert!(out.contains("Program halted after 2 instructions\nip=2 [0 11 0 0 0 0]\n"));
+    assert!(out.contains("Unknown command `foo`"));
+}
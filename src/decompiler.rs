@@ -0,0 +1,820 @@
+//! Turns `#ip` programs back into structured pseudo-code.
+//!
+//! Every instruction that writes to the instruction pointer's register is a jump.  Its target is
+//! found by substituting the instruction's own address for reads of that register: `seti 5 0 ip`
+//! jumps to 6, `addi ip 2 ip` skips the next two instructions and `addr ip rX ip` skips the next
+//! instruction if `rX` is 1, which is how conditional branches are made.  The jumps split the
+//! program into basic blocks, which are then nested into `loop`s and `if`s using the dominator
+//! tree (for loops) and post-dominator tree (for the points where both sides of an `if` meet).
+//! Anything that doesn't fit that shape falls back to `goto`.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use crate::asm_common::*;
+
+/// A value read by an instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Value {
+    Reg(usize),
+    Imm(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BinOp {
+    Add,
+    Mul,
+    And,
+    Or,
+    Gt,
+    Eq,
+}
+
+impl BinOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Mul => "*",
+            BinOp::And => "&",
+            BinOp::Or => "|",
+            BinOp::Gt => ">",
+            BinOp::Eq => "==",
+        }
+    }
+
+    fn is_comparison(self) -> bool { self == BinOp::Gt || self == BinOp::Eq }
+
+    fn apply(self, a: usize, b: usize) -> usize {
+        match self {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Mul => a.wrapping_mul(b),
+            BinOp::And => a & b,
+            BinOp::Or => a | b,
+            BinOp::Gt => (a > b) as usize,
+            BinOp::Eq => (a == b) as usize,
+        }
+    }
+}
+
+/// The value computed by a single instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Expr {
+    Value(Value),
+    Binary(BinOp, Value, Value),
+}
+
+impl Expr {
+    /// Builds the expression computed by `instr` when it is located at `ip`.  Reads of the
+    /// instruction pointer's register are replaced with `ip`, and constant expressions are folded.
+    fn new(instr: Instruction, ip_register: Option<usize>, ip: usize) -> Self {
+        let value = |operand: usize, kind: Operand| match kind {
+            Operand::Register if Some(operand) == ip_register => Value::Imm(ip),
+            Operand::Register => Value::Reg(operand),
+            Operand::Immediate | Operand::Ignored => Value::Imm(operand),
+        };
+        let (kind1, kind2) = instr.opcode.operands();
+        let (a, b) = (value(instr.in1, kind1), value(instr.in2, kind2));
+
+        let op = match instr.opcode {
+            Opcode::Setr | Opcode::Seti => return Expr::Value(a),
+            Opcode::Addr | Opcode::Addi => BinOp::Add,
+            Opcode::Mulr | Opcode::Muli => BinOp::Mul,
+            Opcode::Banr | Opcode::Bani => BinOp::And,
+            Opcode::Borr | Opcode::Bori => BinOp::Or,
+            Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => BinOp::Gt,
+            Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => BinOp::Eq,
+        };
+
+        match (a, b) {
+            (Value::Imm(a), Value::Imm(b)) => Expr::Value(Value::Imm(op.apply(a, b))),
+            _ => Expr::Binary(op, a, b),
+        }
+    }
+
+    fn is_comparison(self) -> bool {
+        match self {
+            Expr::Binary(op, ..) => op.is_comparison(),
+            Expr::Value(_) => false,
+        }
+    }
+
+    fn registers(self) -> impl Iterator<Item = usize> {
+        let (a, b) = match self {
+            Expr::Value(a) => (a, Value::Imm(0)),
+            Expr::Binary(_, a, b) => (a, b),
+        };
+
+        [a, b].to_vec().into_iter().filter_map(|value| match value {
+            Value::Reg(reg) => Some(reg),
+            Value::Imm(_) => None,
+        })
+    }
+}
+
+fn fmt_value(fmt: &mut Formatter, value: Value, hex: bool) -> FmtResult {
+    match value {
+        Value::Reg(reg) => write!(fmt, "r{}", reg),
+        Value::Imm(n) if hex && n > 0xff => write!(fmt, "{:#x}", n),
+        Value::Imm(n) => write!(fmt, "{}", n),
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match *self {
+            Expr::Value(a) => fmt_value(fmt, a, false),
+            Expr::Binary(op, a, b) => {
+                // Masks are a lot easier to recognize in hex
+                let hex = op == BinOp::And || op == BinOp::Or;
+                fmt_value(fmt, a, hex)?;
+                write!(fmt, " {} ", op.symbol())?;
+                fmt_value(fmt, b, hex)
+            },
+        }
+    }
+}
+
+/// `reg = expr`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Assign {
+    pub reg: usize,
+    pub expr: Expr,
+}
+
+impl Display for Assign {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let reg = Value::Reg(self.reg);
+        let other_operand = match self.expr {
+            Expr::Binary(op, a, b) if !op.is_comparison() && a == reg => Some((op, b)),
+            Expr::Binary(op, a, b) if !op.is_comparison() && b == reg => Some((op, a)),
+            _ => None,
+        };
+
+        match other_operand {
+            Some((op, operand)) => {
+                write!(fmt, "r{} {}= ", self.reg, op.symbol())?;
+                fmt_value(fmt, operand, op == BinOp::And || op == BinOp::Or)?;
+                write!(fmt, ";")
+            },
+            None => write!(fmt, "r{} = {};", self.reg, self.expr),
+        }
+    }
+}
+
+/// A branch condition, which holds if `expr` is non-zero (or zero if `negated` is set).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cond {
+    pub expr: Expr,
+    pub negated: bool,
+}
+
+impl Cond {
+    fn negate(self) -> Self {
+        Cond {
+            negated: !self.negated,
+            ..self
+        }
+    }
+}
+
+impl Display for Cond {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match (self.expr, self.negated) {
+            (Expr::Binary(BinOp::Gt, a, b), true) =>
+                write!(fmt, "{} <= {}", Expr::Value(a), Expr::Value(b)),
+            (Expr::Binary(BinOp::Eq, a, b), true) =>
+                write!(fmt, "{} != {}", Expr::Value(a), Expr::Value(b)),
+            (expr, false) if expr.is_comparison() => write!(fmt, "{}", expr),
+            (expr, false) => write!(fmt, "{} != 0", expr),
+            (expr, true) => write!(fmt, "{} == 0", expr),
+        }
+    }
+}
+
+/// How control leaves a basic block.  Targets are instruction addresses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Terminator {
+    Goto(usize),
+    Branch {
+        cond: Cond,
+        then: usize,
+        otherwise: usize,
+        /// Set if the branch was made by adding a register to the instruction pointer without
+        /// that register being the result of a comparison, so it's only correct if the register
+        /// is always 0 or 1.
+        assumes_boolean: bool,
+    },
+    Halt,
+    /// A jump to `expr + 1` that can't be resolved statically
+    Indirect(Expr),
+}
+
+#[derive(Clone, Debug)]
+pub struct Block {
+    /// The address of the first instruction in the block
+    pub start: usize,
+    pub statements: Vec<Assign>,
+    pub terminator: Terminator,
+}
+
+impl Terminator {
+    /// The addresses that control can go to, not including indirect jumps
+    pub fn targets(self) -> Vec<usize> {
+        match self {
+            Terminator::Goto(target) => vec![target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![then, otherwise],
+            Terminator::Halt | Terminator::Indirect(_) => Vec::new(),
+        }
+    }
+}
+
+impl Block {
+    /// The addresses of the blocks that control can flow to from this one
+    pub fn successors(&self) -> Vec<usize> { self.terminator.targets() }
+
+    fn is_bare_halt(&self) -> bool {
+        self.statements.is_empty() && self.terminator == Terminator::Halt
+    }
+}
+
+/// The control-flow graph of a program.  Blocks are sorted by address, and jumps that leave the
+/// program all go to a final empty block that halts.
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+}
+
+/// What an instruction turns into
+enum Decoded {
+    Assign(Assign),
+    Jump(Terminator),
+}
+
+fn decode(instr: Instruction, ip_register: Option<usize>, ip: usize, len: usize) -> Decoded {
+    let expr = Expr::new(instr, ip_register, ip);
+    if Some(instr.out) != ip_register {
+        return Decoded::Assign(Assign {
+            reg: instr.out,
+            expr,
+        });
+    }
+
+    let target = |value: usize| value.wrapping_add(1).min(len);
+    let terminator = match expr {
+        Expr::Value(Value::Imm(value)) => Terminator::Goto(target(value)),
+        Expr::Binary(BinOp::Add, Value::Imm(base), Value::Reg(flag))
+        | Expr::Binary(BinOp::Add, Value::Reg(flag), Value::Imm(base)) => Terminator::Branch {
+            cond: Cond {
+                expr: Expr::Value(Value::Reg(flag)),
+                negated: false,
+            },
+            then: target(base + 1),
+            otherwise: target(base),
+            assumes_boolean: true,
+        },
+        expr => Terminator::Indirect(expr),
+    };
+    Decoded::Jump(terminator)
+}
+
+impl Cfg {
+    pub fn new(program: &Program) -> Self {
+        let len = program.instructions.len();
+        let decoded: Vec<Decoded> = program
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(ip, &instr)| decode(instr, program.ip_register, ip, len))
+            .collect();
+
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        leaders.insert(len);
+        for (ip, decoded) in decoded.iter().enumerate() {
+            if let Decoded::Jump(terminator) = decoded {
+                leaders.insert(ip + 1);
+                leaders.extend(terminator.targets());
+            }
+        }
+
+        let leaders: Vec<usize> = leaders.into_iter().collect();
+        let mut blocks = Vec::with_capacity(leaders.len());
+        for (i, &start) in leaders.iter().enumerate() {
+            let end = leaders.get(i + 1).cloned().unwrap_or(len);
+            let mut statements = Vec::new();
+            let mut terminator = if end < len {
+                Terminator::Goto(end)
+            } else {
+                Terminator::Halt
+            };
+
+            for decoded in &decoded[start..end] {
+                match *decoded {
+                    Decoded::Assign(assign) => statements.push(assign),
+                    Decoded::Jump(jump) => terminator = jump,
+                }
+            }
+
+            // A flag that was just set by a comparison is known to be 0 or 1
+            if let Terminator::Branch {
+                cond:
+                    Cond {
+                        expr: Expr::Value(Value::Reg(flag)),
+                        ..
+                    },
+                ref mut assumes_boolean,
+                ..
+            } = terminator
+            {
+                if let Some(last) = statements.last() {
+                    if last.reg == flag && last.expr.is_comparison() {
+                        *assumes_boolean = false;
+                    }
+                }
+            }
+
+            blocks.push(Block {
+                start,
+                statements,
+                terminator,
+            });
+        }
+
+        let mut cfg = Cfg { blocks };
+        cfg.fold_conditions();
+        cfg
+    }
+
+    fn index_of(&self, address: usize) -> usize {
+        self.blocks
+            .binary_search_by_key(&address, |block| block.start)
+            .expect("Jump into the middle of a block")
+    }
+
+    fn successor_indices(&self) -> Vec<Vec<usize>> {
+        self.blocks
+            .iter()
+            .map(|block| {
+                block
+                    .successors()
+                    .into_iter()
+                    .map(|address| self.index_of(address))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Computes the registers that are live on entry to each block as bitmasks.  Only `r0` is
+    /// considered to be live when the program halts since that's where every puzzle wants its
+    /// answer, while indirect jumps could go anywhere so everything is live before them.
+    fn live_in(&self) -> Vec<u64> {
+        let succs = self.successor_indices();
+        let all_registers = (1u64 << REGISTER_COUNT) - 1;
+        let mut live_in = vec![0u64; self.blocks.len()];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, block) in self.blocks.iter().enumerate().rev() {
+                let mut live = match block.terminator {
+                    Terminator::Halt => 1,
+                    Terminator::Indirect(_) => all_registers,
+                    _ => succs[i].iter().fold(0, |live, &succ| live | live_in[succ]),
+                };
+                match block.terminator {
+                    Terminator::Branch { cond, .. } =>
+                        live |= cond.expr.registers().fold(0, |mask, reg| mask | 1 << reg),
+                    Terminator::Indirect(expr) =>
+                        live |= expr.registers().fold(0, |mask, reg| mask | 1 << reg),
+                    _ => (),
+                }
+
+                for statement in block.statements.iter().rev() {
+                    live &= !(1 << statement.reg);
+                    live |= statement
+                        .expr
+                        .registers()
+                        .fold(0, |mask, reg| mask | 1 << reg);
+                }
+
+                if live != live_in[i] {
+                    live_in[i] = live;
+                    changed = true;
+                }
+            }
+        }
+
+        live_in
+    }
+
+    /// Moves comparisons into the branches that use them if the flag register they set isn't
+    /// read anywhere else, so `r2 = r3 == r4; if r2 != 0` becomes `if r3 == r4`.
+    fn fold_conditions(&mut self) {
+        let live_in = self.live_in();
+
+        for i in 0..self.blocks.len() {
+            let (then, otherwise) = match self.blocks[i].terminator {
+                Terminator::Branch {
+                    then, otherwise, ..
+                } => (self.index_of(then), self.index_of(otherwise)),
+                _ => continue,
+            };
+            let block = &mut self.blocks[i];
+            let last = match block.statements.last() {
+                Some(&last) => last,
+                None => continue,
+            };
+
+            if let Terminator::Branch { ref mut cond, .. } = block.terminator {
+                let flag_is_dead = (live_in[then] | live_in[otherwise]) & (1 << last.reg) == 0;
+                if cond.expr == Expr::Value(Value::Reg(last.reg))
+                    && last.expr.is_comparison()
+                    && flag_is_dead
+                {
+                    cond.expr = last.expr;
+                    block.statements.pop();
+                }
+            }
+        }
+    }
+}
+
+/// For each node, the set of nodes that all paths from `roots` to it go through (including
+/// itself).  Nodes that can't be reached from `roots` are dominated by every node.
+fn dominators(preds: &[Vec<usize>], roots: &[usize]) -> Vec<HashSet<usize>> {
+    let all: HashSet<usize> = (0..preds.len()).collect();
+    let mut doms = vec![all; preds.len()];
+    for &root in roots {
+        doms[root] = Some(root).into_iter().collect();
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in 0..preds.len() {
+            if roots.contains(&node) {
+                continue;
+            }
+
+            let mut dom = preds[node]
+                .iter()
+                .map(|&pred| doms[pred].clone())
+                .fold(None, |acc: Option<HashSet<usize>>, set| match acc {
+                    Some(acc) => Some(acc.intersection(&set).cloned().collect()),
+                    None => Some(set),
+                })
+                .unwrap_or_else(|| doms[node].clone());
+            dom.insert(node);
+
+            if dom != doms[node] {
+                doms[node] = dom;
+                changed = true;
+            }
+        }
+    }
+
+    doms
+}
+
+/// Picks the closest strict dominator of each node out of the full dominator sets.
+fn immediate_dominators(doms: &[HashSet<usize>]) -> Vec<Option<usize>> {
+    (0..doms.len())
+        .map(|node| {
+            let strict: HashSet<usize> =
+                doms[node].iter().cloned().filter(|&d| d != node).collect();
+            strict.iter().cloned().find(|&d| doms[d] == strict)
+        })
+        .collect()
+}
+
+struct Loop {
+    body: HashSet<usize>,
+    /// Where control goes once the loop is done, if there's a single obvious place
+    follow: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+struct LoopCtx {
+    header: usize,
+    follow: Option<usize>,
+}
+
+struct Structurer<'a> {
+    cfg: &'a Cfg,
+    succs: Vec<Vec<usize>>,
+    ipdom: Vec<Option<usize>>,
+    loops: HashMap<usize, Loop>,
+    /// Blocks that need a label because something jumps to them with a `goto`
+    labels: BTreeSet<usize>,
+    goto_targets: BTreeSet<usize>,
+    emitted: Vec<bool>,
+    out: String,
+}
+
+impl<'a> Structurer<'a> {
+    fn new(cfg: &'a Cfg) -> Self {
+        let succs = cfg.successor_indices();
+        let block_count = cfg.blocks.len();
+
+        let mut preds = vec![Vec::new(); block_count];
+        for (block, block_succs) in succs.iter().enumerate() {
+            for &succ in block_succs {
+                preds[succ].push(block);
+            }
+        }
+
+        let doms = dominators(&preds, &[0]);
+        let exits: Vec<usize> = (0..block_count).filter(|&i| succs[i].is_empty()).collect();
+        let post_doms = dominators(&succs, &exits);
+        let ipdom = immediate_dominators(&post_doms);
+
+        // Natural loops: a back-edge is a jump to a block that dominates the jumping block.
+        let mut reachable = vec![false; block_count];
+        let mut stack = vec![0];
+        while let Some(block) = stack.pop() {
+            if !reachable[block] {
+                reachable[block] = true;
+                stack.extend(&succs[block]);
+            }
+        }
+
+        let mut loops: HashMap<usize, Loop> = HashMap::new();
+        for (tail, tail_succs) in succs.iter().enumerate() {
+            for &header in tail_succs {
+                if !reachable[tail] || !doms[tail].contains(&header) {
+                    continue;
+                }
+
+                let lp = loops.entry(header).or_insert_with(|| Loop {
+                    body: Some(header).into_iter().collect(),
+                    follow: None,
+                });
+                let mut stack = vec![tail];
+                while let Some(block) = stack.pop() {
+                    if lp.body.insert(block) {
+                        stack.extend(&preds[block]);
+                    }
+                }
+            }
+        }
+        for lp in loops.values_mut() {
+            lp.follow = lp
+                .body
+                .iter()
+                .flat_map(|&block| succs[block].iter().cloned())
+                .filter(|succ| !lp.body.contains(succ))
+                .min();
+        }
+
+        Structurer {
+            cfg,
+            succs,
+            ipdom,
+            loops,
+            labels: BTreeSet::new(),
+            goto_targets: BTreeSet::new(),
+            emitted: vec![false; block_count],
+            out: String::new(),
+        }
+    }
+
+    fn line(&mut self, depth: usize, line: &str) {
+        for _ in 0..depth {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn is_bare_halt(&self, block: usize) -> bool { self.cfg.blocks[block].is_bare_halt() }
+
+    fn address(&self, block: usize) -> usize { self.cfg.blocks[block].start }
+
+    /// The statement that leaves the current sequence if control goes to `block`, if it does
+    fn exit_statement(&self, block: usize, lp: Option<LoopCtx>) -> Option<&'static str> {
+        match lp {
+            Some(lp) if block == lp.header => Some("continue;"),
+            Some(lp) if Some(block) == lp.follow => Some("break;"),
+            _ if self.is_bare_halt(block) => Some("halt;"),
+            _ => None,
+        }
+    }
+
+    /// Emits code starting at `block` until control reaches `stop` or leaves the sequence.  If
+    /// `tail` is set, going back to the top of the current loop is implicit.
+    fn emit_seq(
+        &mut self,
+        mut block: usize,
+        stop: Option<usize>,
+        lp: Option<LoopCtx>,
+        depth: usize,
+        tail: bool,
+    ) {
+        loop {
+            if Some(block) == stop {
+                return;
+            }
+            if let Some(statement) = self.exit_statement(block, lp) {
+                if !(tail && statement == "continue;") {
+                    self.line(depth, statement);
+                }
+                return;
+            }
+            if self.emitted[block] {
+                self.goto_targets.insert(block);
+                let line = format!("goto L{};", self.address(block));
+                self.line(depth, &line);
+                return;
+            }
+
+            if self.loops.contains_key(&block) {
+                let inner = LoopCtx {
+                    header: block,
+                    follow: self.loops[&block].follow,
+                };
+                self.line(depth, "loop {");
+                if let Some(next) = self.emit_block(block, Some(inner), depth + 1) {
+                    self.emit_seq(next, None, Some(inner), depth + 1, true);
+                }
+                self.line(depth, "}");
+
+                match inner.follow {
+                    Some(follow) => block = follow,
+                    None => return,
+                }
+                continue;
+            }
+
+            match self.emit_block(block, lp, depth) {
+                Some(next) => block = next,
+                None => return,
+            }
+        }
+    }
+
+    /// Renders a sequence into a separate buffer, used for the arms of `if`s.
+    fn render_arm(
+        &mut self,
+        block: usize,
+        stop: Option<usize>,
+        lp: Option<LoopCtx>,
+        depth: usize,
+    ) -> String {
+        let outer = std::mem::replace(&mut self.out, String::new());
+        self.emit_seq(block, stop, lp, depth, false);
+        std::mem::replace(&mut self.out, outer)
+    }
+
+    /// Emits a single block and returns the block that control continues to afterwards, if
+    /// there's a single one.
+    fn emit_block(&mut self, block: usize, lp: Option<LoopCtx>, depth: usize) -> Option<usize> {
+        self.emitted[block] = true;
+        if self.labels.contains(&block) {
+            let label = format!("L{}:", self.address(block));
+            self.line(depth.saturating_sub(1), &label);
+        }
+
+        let cfg = self.cfg;
+        for statement in &cfg.blocks[block].statements {
+            self.line(depth, &statement.to_string());
+        }
+
+        match cfg.blocks[block].terminator {
+            Terminator::Halt => {
+                self.line(depth, "halt;");
+                None
+            },
+            Terminator::Indirect(expr) => {
+                self.line(depth, &format!("goto ({}) + 1;", expr));
+                None
+            },
+            Terminator::Goto(_) => Some(self.succs[block][0]),
+            Terminator::Branch {
+                cond,
+                assumes_boolean,
+                ..
+            } => {
+                let (then, otherwise) = (self.succs[block][0], self.succs[block][1]);
+                let assumption = match cond.expr {
+                    Expr::Value(value) if assumes_boolean =>
+                        format!(" // assumes {} is 0 or 1", Expr::Value(value)),
+                    _ => String::new(),
+                };
+
+                if let Some(statement) = self.exit_statement(then, lp) {
+                    self.line(
+                        depth,
+                        &format!("if {} {{ {} }}{}", cond, statement, assumption),
+                    );
+                    return Some(otherwise);
+                }
+                if let Some(statement) = self.exit_statement(otherwise, lp) {
+                    let line = format!("if {} {{ {} }}{}", cond.negate(), statement, assumption);
+                    self.line(depth, &line);
+                    return Some(then);
+                }
+
+                // Both sides meet again at the immediate post-dominator, unless that's outside of
+                // the current loop, in which case both sides have to leave on their own.
+                let merge = self.ipdom[block].filter(|merge| {
+                    lp.map_or(true, |lp| self.loops[&lp.header].body.contains(merge))
+                });
+                let then_code = self.render_arm(then, merge, lp, depth + 1);
+                let otherwise_code = self.render_arm(otherwise, merge, lp, depth + 1);
+
+                let (cond, then_code, otherwise_code) = if then_code.is_empty() {
+                    (cond.negate(), otherwise_code, then_code)
+                } else {
+                    (cond, then_code, otherwise_code)
+                };
+                self.line(depth, &format!("if {} {{{}", cond, assumption));
+                self.out.push_str(&then_code);
+                if !otherwise_code.is_empty() {
+                    self.line(depth, "} else {");
+                    self.out.push_str(&otherwise_code);
+                }
+                self.line(depth, "}");
+
+                merge
+            },
+        }
+    }
+
+    fn run(&mut self) -> String {
+        self.emitted = vec![false; self.cfg.blocks.len()];
+        self.goto_targets.clear();
+        self.out.clear();
+        self.emit_seq(0, None, None, 0, false);
+        self.out.clone()
+    }
+}
+
+/// Decompiles `program` into structured pseudo-code.  Registers are named `r0` through `r5`; the
+/// instruction pointer's register never shows up because all reads of it are replaced by
+/// constants and all writes to it by control flow.
+pub fn decompile(program: &Program) -> String {
+    let cfg = Cfg::new(program);
+    let mut structurer = Structurer::new(&cfg);
+
+    // The first pass finds out which blocks are the targets of `goto`s so that the second one can
+    // label them.
+    structurer.run();
+    structurer.labels = structurer.goto_targets.clone();
+    structurer.run()
+}
+
+#[test]
+fn decompile_loops_and_ifs() {
+    let program: Program = "#ip 4
+seti 0 0 1
+addi 1 1 1
+bani 1 1 2
+eqri 2 0 2
+addr 4 2 4
+addi 0 1 0
+gtri 1 9 2
+addr 2 4 4
+seti 0 0 4
+mulr 4 4 4
+"
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        decompile(&program),
+        "\
+r1 = 0;
+loop {
+    r1 += 1;
+    r2 = r1 & 1;
+    if r2 != 0 {
+        r0 += 1;
+    }
+    if r1 > 9 { break; }
+}
+halt;
+"
+    );
+}
+
+#[test]
+fn decompile_day19() {
+    let program: Program = include_str!("../input/day19.txt").parse().unwrap();
+    let code = decompile(&program);
+
+    assert!(code.contains(
+        "    loop {
+        r2 = r3 * r5;
+        if r2 == r4 {
+            r0 += r3;
+        }
+        r5 += 1;
+        if r5 > r4 { break; }
+    }
+"
+    ));
+    assert!(code.contains("if r0 != 0 { // assumes r0 is 0 or 1"));
+}
@@ -22,9 +22,36 @@ extern crate nom;
 extern crate cached;
 extern crate z3;
 
+use std::{
+    any::Any,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    ops::RangeInclusive,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
 use structopt::StructOpt;
 
+use crate::{
+    asm_common::{Program, VM},
+    day15::{replay::Replay, Battle, Stats},
+    decompiler::decompile,
+    solution::{solve, Answers, Parts, Runner, Solution},
+    timing::DayReport,
+    verify::ExpectedAnswers,
+};
+
 pub mod asm_common;
+pub mod compiler;
 pub mod day1;
 pub mod day10;
 pub mod day11;
@@ -50,55 +77,476 @@ pub mod day6;
 pub mod day7;
 pub mod day8;
 pub mod day9;
+pub mod debugger;
+pub mod decompiler;
+pub mod optimizer;
+pub mod solution;
+pub mod symbolic;
+pub mod timing;
+pub mod verify;
 
 fn print_day(i: usize) {
     println!("== DAY {} ==", i);
 }
 
-const DAYS: &[fn()] = &[
-    day1::run,
-    day2::run,
-    day3::run,
-    day4::run,
-    day5::run,
-    day6::run,
-    day7::run,
-    day8::run,
-    day9::run,
-    day10::run,
-    day11::run,
-    day12::run,
-    day13::run,
-    day14::run,
-    day15::run,
-    day16::run,
-    day17::run,
-    day18::run,
-    day19::run,
-    day20::run,
-    day21::run,
-    day22::run,
-    day23::run,
-    day24::run,
-    day25::run,
+const DAYS: &[Runner] = &[
+    solve::<day1::Day1>,
+    solve::<day2::Day2>,
+    solve::<day3::Day3>,
+    solve::<day4::Day4>,
+    solve::<day5::Day5>,
+    solve::<day6::Day6>,
+    solve::<day7::Day7>,
+    solve::<day8::Day8>,
+    solve::<day9::Day9>,
+    solve::<day10::Day10>,
+    solve::<day11::Day11>,
+    solve::<day12::Day12>,
+    solve::<day13::Day13>,
+    solve::<day14::Day14>,
+    solve::<day15::Day15>,
+    solve::<day16::Day16>,
+    solve::<day17::Day17>,
+    solve::<day18::Day18>,
+    solve::<day19::Day19>,
+    solve::<day20::Day20>,
+    solve::<day21::Day21>,
+    solve::<day22::Day22>,
+    solve::<day23::Day23>,
+    solve::<day24::Day24>,
+    solve::<day25::Day25>,
 ];
 
+#[derive(StructOpt)]
+enum Command {
+    /// Steps through an `#ip` program interactively.  Type `help` at the prompt for a list of
+    /// commands.
+    #[structopt(name = "debug")]
+    Debug {
+        /// The program to debug.  Defaults to the embedded day 19 input.
+        #[structopt(parse(from_os_str))]
+        program: Option<PathBuf>,
+    },
+    /// Prints structured pseudo-code for an `#ip` program.
+    #[structopt(name = "decompile")]
+    Decompile {
+        /// The program to decompile.  Defaults to the embedded day 19 input.
+        #[structopt(parse(from_os_str))]
+        program: Option<PathBuf>,
+    },
+    /// Records a day 15 battle round by round so that it can be watched with `replay`.
+    #[structopt(name = "record")]
+    Record {
+        /// Where to write the replay
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+        /// The battle map.  Defaults to the embedded day 15 input.
+        #[structopt(long = "map", parse(from_os_str))]
+        map: Option<PathBuf>,
+        /// The elves' attack power
+        #[structopt(long = "elf-power", default_value = "3")]
+        elf_power: usize,
+    },
+    /// Plays back a day 15 replay in the terminal.  Type a control followed by enter while it's
+    /// playing; they're listed below the battlefield.
+    #[structopt(name = "replay")]
+    Replay {
+        /// The replay to play, as written by `record`
+        #[structopt(parse(from_os_str))]
+        replay: PathBuf,
+        /// How many milliseconds each round is shown for to begin with, from 10 to 10000
+        #[structopt(long = "delay", default_value = "300")]
+        delay: u64,
+    },
+}
+
 #[derive(StructOpt)]
 struct Args {
-    #[structopt(short = "d", long = "day")]
-    pub days: Vec<usize>,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+    /// The days to run, either single days or inclusive ranges like `15-20`.  Runs every day if
+    /// none are given.
+    #[structopt(short = "d", long = "day", parse(try_from_str = "parse_days"))]
+    pub days: Vec<RangeInclusive<usize>>,
+    /// Only runs this part of each day.
+    #[structopt(short = "p", long = "part", parse(try_from_str = "parse_part"))]
+    pub part: Option<Parts>,
+    /// Reads the puzzle input from this file instead of using the embedded one.  Pass `-` to read
+    /// it from stdin.  Requires exactly one `--day`.
+    #[structopt(short = "i", long = "input", parse(from_os_str))]
+    pub input: Option<PathBuf>,
+    /// Checks the answers against the expected ones rather than printing them.  Exits with a
+    /// non-zero status code if any of them are wrong.
+    #[structopt(long = "verify")]
+    pub verify: bool,
+    /// Reads the expected answers for `--verify` from this file instead of the embedded
+    /// `answers.txt`.
+    #[structopt(long = "answers", parse(from_os_str))]
+    pub answers: Option<PathBuf>,
+    /// Prints what some days can tell about how they found their answers, like how a day 15
+    /// battle played out, after the answers.
+    #[structopt(long = "report", conflicts_with = "time")]
+    pub report: bool,
+    /// Times parsing and both parts of each day over `--iterations` runs and prints the minimum,
+    /// median and maximum of each instead of the answers.
+    #[structopt(long = "time", conflicts_with = "verify")]
+    pub time: bool,
+    /// The number of runs per day for `--time`
+    #[structopt(short = "n", long = "iterations", default_value = "5")]
+    pub iterations: usize,
+    /// Prints the `--time` report as JSON instead of a table.
+    #[structopt(long = "json", requires = "time")]
+    pub json: bool,
+    /// Runs up to this many days at once on their own threads.  Results are still printed in day
+    /// order, and a day that panics is reported without stopping the others.
+    #[structopt(short = "j", long = "jobs", conflicts_with = "time")]
+    pub jobs: Option<usize>,
+}
+
+fn parse_day(s: &str) -> Result<usize, String> {
+    match s.trim().parse() {
+        Ok(day) if day >= 1 && day <= DAYS.len() => Ok(day),
+        _ => Err(format!(
+            "Invalid day `{}`; expected a number from 1 to {}",
+            s,
+            DAYS.len()
+        )),
+    }
+}
+
+fn parse_days(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let mut bounds = s.splitn(2, '-');
+    let start = parse_day(bounds.next().unwrap())?;
+    let end = match bounds.next() {
+        Some(end) => parse_day(end)?,
+        None => start,
+    };
+
+    if start > end {
+        return Err(format!(
+            "Invalid day range `{}`; the start is after the end",
+            s
+        ));
+    }
+    Ok(start..=end)
+}
+
+fn parse_part(s: &str) -> Result<Parts, String> {
+    match s {
+        "1" => Ok(Parts::Part1),
+        "2" => Ok(Parts::Part2),
+        _ => Err(format!("Invalid part `{}`; expected 1 or 2", s)),
+    }
+}
+
+/// Turns the payload of a caught panic back into its message.
+fn panic_message(payload: &(Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => (*msg).to_owned(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "Unknown panic".to_owned()),
+    }
+}
+
+/// Runs `days` on `jobs` dedicated threads, so at most that many days run at once while the
+/// global rayon pool that the days use internally is left alone.  `handle` is called with the
+/// answers of each day in the order they were given, as soon as that day and every one before it
+/// are done.  A day that panics is handed over as its panic message rather than ending the run.
+fn run_parallel(
+    days: &[usize],
+    input: Option<&str>,
+    parts: Parts,
+    jobs: usize,
+    mut handle: impl FnMut(usize, Result<Answers, String>),
+) {
+    let days = Arc::new(days.to_vec());
+    let input = Arc::new(input.map(str::to_owned));
+    let next_ix = Arc::new(AtomicUsize::new(0));
+
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..jobs.min(days.len()) {
+        let (tx, days, input, next_ix) = (tx.clone(), days.clone(), input.clone(), next_ix.clone());
+        thread::spawn(move || loop {
+            let ix = next_ix.fetch_add(1, Ordering::SeqCst);
+            let day = match days.get(ix) {
+                Some(&day) => day,
+                None => break,
+            };
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                DAYS[day - 1](input.as_ref().as_ref().map(String::as_str), parts).0
+            }))
+            .map_err(|payload| panic_message(&*payload));
+            // The receiver only goes away if the main thread is gone, so there's no one to tell.
+            if tx.send((ix, result)).is_err() {
+                break;
+            }
+        });
+    }
+    drop(tx);
+
+    let mut finished = HashMap::new();
+    let mut next_handled = 0;
+    for (ix, result) in rx {
+        finished.insert(ix, result);
+        while let Some(result) = finished.remove(&next_handled) {
+            handle(days[next_handled], result);
+            next_handled += 1;
+        }
+    }
+}
+
+fn read_input(path: &Path) -> io::Result<String> {
+    let mut input = String::new();
+    if path == Path::new("-") {
+        io::stdin().read_to_string(&mut input)?;
+    } else {
+        File::open(path)?.read_to_string(&mut input)?;
+    }
+
+    Ok(input)
+}
+
+fn load_program(program_path: Option<&Path>) -> Program {
+    let source = match program_path {
+        Some(path) => read_input(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read program from {}: {}", path.display(), err);
+            process::exit(1);
+        }),
+        None => <day19::Day19 as Solution>::INPUT.to_owned(),
+    };
+    source.parse().unwrap_or_else(|err| {
+        eprintln!("Invalid program: {}", err);
+        process::exit(1);
+    })
+}
+
+fn debug(program: &Program) {
+    let stdin = io::stdin();
+    if let Err(err) = debugger::run(VM::new(program), stdin.lock(), io::stdout()) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn record(map_path: Option<&Path>, elf_power: usize, output: &Path) {
+    let map = match map_path {
+        Some(path) => read_input(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read map from {}: {}", path.display(), err);
+            process::exit(1);
+        }),
+        None => <day15::Day15 as Solution>::INPUT.to_owned(),
+    };
+    let elves = Stats {
+        attack_power: elf_power,
+        ..Stats::default()
+    };
+    let map = day15::parse_map(&map).unwrap_or_else(|err| {
+        eprintln!("Invalid map: {}", err);
+        process::exit(1);
+    });
+    let mut battle = Battle::new(&map, elves, Stats::default());
+    battle.enable_recording();
+    while battle.step_round() {}
+
+    if let Err(err) = fs::write(output, battle.replay().unwrap().to_string()) {
+        eprintln!("Failed to write replay to {}: {}", output.display(), err);
+        process::exit(1);
+    }
+    println!(
+        "Recorded {} full rounds to {}",
+        battle.rounds(),
+        output.display()
+    );
+}
+
+fn replay(path: &Path, delay: Duration) {
+    let replay: Replay = read_input(path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| {
+            source
+                .parse()
+                .map_err(|err| format!("Invalid replay: {}", err))
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load replay from {}: {}", path.display(), err);
+            process::exit(1);
+        });
+
+    let input = BufReader::new(io::stdin());
+    if let Err(err) = day15::replay::play(&replay, input, io::stdout(), delay) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
 
 pub fn main() {
     let opt = Args::from_args();
-    let days_iterator: Box<Iterator<Item = (usize, &'static fn())>> = if opt.days.is_empty() {
-        box DAYS.iter().enumerate()
+
+    match opt.command {
+        Some(Command::Debug { ref program }) => {
+            debug(&load_program(program.as_ref().map(PathBuf::as_path)));
+            return;
+        },
+        Some(Command::Decompile { ref program }) => {
+            print!(
+                "{}",
+                decompile(&load_program(program.as_ref().map(PathBuf::as_path)))
+            );
+            return;
+        },
+        Some(Command::Record {
+            ref output,
+            ref map,
+            elf_power,
+        }) => {
+            record(map.as_ref().map(PathBuf::as_path), elf_power, output);
+            return;
+        },
+        Some(Command::Replay { ref replay, delay }) => {
+            self::replay(replay, Duration::from_millis(delay));
+            return;
+        },
+        None => (),
+    }
+
+    let days: Vec<usize> = if opt.days.is_empty() {
+        (1..=DAYS.len()).collect()
+    } else {
+        opt.days.iter().cloned().flatten().collect()
+    };
+    let parts = opt.part.unwrap_or(Parts::Both);
+
+    let custom_input = opt.input.as_ref().map(|path| {
+        if days.len() != 1 {
+            eprintln!("`--input` can only be used when running a single day");
+            process::exit(1);
+        }
+
+        read_input(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read input from {}: {}", path.display(), err);
+            process::exit(1);
+        })
+    });
+
+    let expected_answers = if opt.verify {
+        let answers = match opt.answers {
+            Some(ref path) => read_input(path).unwrap_or_else(|err| {
+                eprintln!("Failed to read answers from {}: {}", path.display(), err);
+                process::exit(1);
+            }),
+            None => verify::ANSWERS.to_owned(),
+        };
+        let expected_answers = ExpectedAnswers::parse(&answers).unwrap_or_else(|err| {
+            eprintln!("Invalid answers file: {}", err);
+            process::exit(1);
+        });
+        Some(expected_answers)
     } else {
-        box opt.days.into_iter().map(|i| (i - 1, &DAYS[i - 1]))
+        None
+    };
+
+    if opt.jobs == Some(0) {
+        eprintln!("`--jobs` must be at least 1");
+        process::exit(1);
+    }
+
+    let days_iterator = days.iter().map(|&day| (day, DAYS[day - 1]));
+    let custom_input = custom_input.as_ref().map(String::as_str);
+
+    if opt.time {
+        if opt.iterations == 0 {
+            eprintln!("`--iterations` must be at least 1");
+            process::exit(1);
+        }
+
+        if !opt.json {
+            timing::print_table_header();
+        }
+        let mut reports = Vec::new();
+        for (day, runner) in days_iterator {
+            let samples: Vec<_> = (0..opt.iterations)
+                .map(|_| runner(custom_input, parts).1)
+                .collect();
+            let report = DayReport::new(day, &samples);
+            if !opt.json {
+                timing::print_table_rows(&report);
+            }
+            reports.push(report);
+        }
+
+        if opt.json {
+            println!("{}", timing::to_json(&reports));
+        }
+        return;
+    }
+
+    let mut failures = 0;
+    let mut panics = 0;
+    let mut handle_answers = |day: usize, result: Result<Answers, String>| {
+        print_day(day);
+        let answers = match result {
+            Ok(answers) => answers,
+            Err(msg) => {
+                println!("Panicked: {}", msg);
+                panics += 1;
+                return;
+            },
+        };
+
+        match expected_answers {
+            Some(ref expected_answers) =>
+                failures += verify::report(expected_answers, day, &answers),
+            None => {
+                if let Some(part1) = answers.part1 {
+                    println!("Part 1: {}", part1);
+                }
+                if let Some(part2) = answers.part2 {
+                    println!("Part 2: {}", part2);
+                }
+            },
+        }
+
+        if opt.report {
+            for (part, report) in [answers.part1_report, answers.part2_report]
+                .iter()
+                .enumerate()
+            {
+                if let Some(report) = report {
+                    print!("Part {} report:\n{}", part + 1, report);
+                }
+            }
+        }
     };
 
-    for (i, day) in days_iterator {
-        print_day(i + 1);
-        day();
+    if let Some(jobs) = opt.jobs {
+        run_parallel(&days, custom_input, parts, jobs, &mut handle_answers);
+    } else {
+        for (day, runner) in days_iterator {
+            handle_answers(day, Ok(runner(custom_input, parts).0));
+        }
     }
+
+    if panics > 0 {
+        eprintln!("{} day(s) panicked", panics);
+        process::exit(1);
+    }
+    if failures > 0 {
+        eprintln!("{} answer(s) did not match the expected ones", failures);
+        process::exit(1);
+    }
+}
+
+#[test]
+fn day_selection_parsing() {
+    assert_eq!(parse_days("7"), Ok(7..=7));
+    assert_eq!(parse_days("15-20"), Ok(15..=20));
+    assert!(parse_days("0").is_err());
+    assert!(parse_days("26").is_err());
+    assert!(parse_days("20-15").is_err());
+    assert!(parse_days("3-").is_err());
+    assert_eq!(parse_part("2"), Ok(Parts::Part2));
+    assert!(parse_part("3").is_err());
 }
@@ -0,0 +1,321 @@
+//! Finds loops in `#ip` programs that are too slow to interpret and attaches super-instructions
+//! that compute their effects directly.
+//!
+//! Loops are matched on their exact instruction sequence, with any choice of registers.  The
+//! original instructions are kept, so jumping into the middle of a loop still works; the
+//! super-instruction only runs when control reaches the first instruction of the loop.
+
+use crate::asm_common::*;
+
+/// Returns `(in1, in2, out)` if `instr` has the given opcode.
+fn operands(instr: Instruction, opcode: Opcode) -> Option<(usize, usize, usize)> {
+    if instr.opcode == opcode {
+        Some((instr.in1, instr.in2, instr.out))
+    } else {
+        None
+    }
+}
+
+/// Matches `addr flag ip ip` or `addr ip flag ip`, which skips the next instruction if `flag` is
+/// 1, returning `flag`.
+fn skip_if(instr: Instruction, ip: usize) -> Option<usize> {
+    let (a, b, out) = operands(instr, Opcode::Addr)?;
+    match (a == ip, b == ip, out == ip) {
+        (true, false, true) => Some(b),
+        (false, true, true) => Some(a),
+        _ => None,
+    }
+}
+
+/// Matches `addi ip 1 ip`, which unconditionally skips the next instruction.
+fn is_skip(instr: Instruction, ip: usize) -> bool {
+    operands(instr, Opcode::Addi) == Some((ip, 1, ip))
+}
+
+/// Matches `seti target-1 _ ip`, returning the address it jumps to.
+fn jump_target(instr: Instruction, ip: usize) -> Option<usize> {
+    match operands(instr, Opcode::Seti) {
+        Some((target, _, out)) if out == ip => Some(target + 1),
+        _ => None,
+    }
+}
+
+/// Picks the operand of a commutative instruction that isn't `known`.
+fn other_operand(a: usize, b: usize, known: usize) -> Option<usize> {
+    if a == known {
+        Some(b)
+    } else if b == known {
+        Some(a)
+    } else {
+        None
+    }
+}
+
+fn all_distinct(registers: &[usize]) -> bool {
+    registers
+        .iter()
+        .enumerate()
+        .all(|(i, reg)| !registers[i + 1..].contains(reg))
+}
+
+/// ```text
+/// p:   mulr factor counter flag
+///      eqrr flag target flag
+///      addr flag ip ip
+///      addi ip 1 ip
+///      addr factor acc acc
+///      addi counter 1 counter
+///      gtrr counter target flag
+///      addr ip flag ip
+///      seti p-1 _ ip
+/// ```
+fn match_divisor_step(code: &[Instruction], p: usize, ip: usize) -> Option<Fused> {
+    let c = code.get(p..p + 9)?;
+
+    let (mul1, mul2, flag) = operands(c[0], Opcode::Mulr)?;
+    let (eq1, eq2, eq_out) = operands(c[1], Opcode::Eqrr)?;
+    let target = other_operand(eq1, eq2, flag)?;
+    let (add1, add2, acc) = operands(c[4], Opcode::Addr)?;
+    let factor = other_operand(add1, add2, acc)?;
+    let counter = other_operand(mul1, mul2, factor)?;
+
+    let matches = eq_out == flag
+        && skip_if(c[2], ip) == Some(flag)
+        && is_skip(c[3], ip)
+        && operands(c[5], Opcode::Addi) == Some((counter, 1, counter))
+        && operands(c[6], Opcode::Gtrr) == Some((counter, target, flag))
+        && skip_if(c[7], ip) == Some(flag)
+        && jump_target(c[8], ip) == Some(p)
+        && all_distinct(&[factor, counter, target, acc, flag, ip]);
+
+    if matches {
+        Some(Fused::DivisorStep {
+            factor,
+            counter,
+            target,
+            acc,
+            flag,
+            exit: p + 9,
+        })
+    } else {
+        None
+    }
+}
+
+/// A divisor step loop wrapped in one that runs it for every factor from its initial value up to
+/// the target:
+///
+/// ```text
+/// p:   seti 1 _ inner
+///      <divisor step loop>
+///      addi counter 1 counter
+///      gtrr counter target flag
+///      addr flag ip ip
+///      seti p-1 _ ip
+/// ```
+fn match_divisor_sum(code: &[Instruction], p: usize, ip: usize) -> Option<Fused> {
+    let c = code.get(p..p + 14)?;
+    let (factor, inner, target, acc, flag) = match match_divisor_step(code, p + 1, ip)? {
+        Fused::DivisorStep {
+            factor,
+            counter,
+            target,
+            acc,
+            flag,
+            ..
+        } => (factor, counter, target, acc, flag),
+        _ => return None,
+    };
+
+    let matches = operands(c[0], Opcode::Seti).map(|(value, _, out)| (value, out))
+        == Some((1, inner))
+        && operands(c[10], Opcode::Addi) == Some((factor, 1, factor))
+        && operands(c[11], Opcode::Gtrr) == Some((factor, target, flag))
+        && skip_if(c[12], ip) == Some(flag)
+        && jump_target(c[13], ip) == Some(p);
+
+    if matches {
+        Some(Fused::DivisorSum {
+            counter: factor,
+            inner,
+            target,
+            acc,
+            flag,
+            exit: p + 14,
+        })
+    } else {
+        None
+    }
+}
+
+/// ```text
+/// p:   addi quotient 1 flag
+///      muli flag divisor flag
+///      gtrr flag dividend flag
+///      addr flag ip ip
+///      addi ip 1 ip
+///      seti exit-1 _ ip
+///      addi quotient 1 quotient
+///      seti p-1 _ ip
+/// ```
+fn match_divide(code: &[Instruction], p: usize, ip: usize) -> Option<Fused> {
+    let c = code.get(p..p + 8)?;
+
+    let (quotient, _, flag) = operands(c[0], Opcode::Addi).filter(|&(_, one, _)| one == 1)?;
+    let divisor = operands(c[1], Opcode::Muli)
+        .filter(|&(a, _, out)| a == flag && out == flag)
+        .map(|(_, divisor, _)| divisor)?;
+    let dividend = operands(c[2], Opcode::Gtrr)
+        .filter(|&(a, _, out)| a == flag && out == flag)
+        .map(|(_, dividend, _)| dividend)?;
+    let exit = jump_target(c[5], ip)?;
+
+    let matches = divisor != 0
+        && skip_if(c[3], ip) == Some(flag)
+        && is_skip(c[4], ip)
+        && operands(c[6], Opcode::Addi) == Some((quotient, 1, quotient))
+        && jump_target(c[7], ip) == Some(p)
+        && all_distinct(&[quotient, dividend, flag, ip]);
+
+    if matches {
+        Some(Fused::Divide {
+            quotient,
+            dividend,
+            divisor,
+            flag,
+            exit,
+        })
+    } else {
+        None
+    }
+}
+
+/// Attaches super-instructions to every loop in `instructions` that has one, replacing any that
+/// were there before.  Returns the number of loops that were found.  Programs without an
+/// instruction pointer register can't jump, so there's nothing to do for them.
+pub fn optimize(instructions: &mut Instructions, ip_register: Option<usize>) -> usize {
+    let ip = match ip_register {
+        Some(ip) => ip,
+        None => return 0,
+    };
+
+    let code = &instructions.instructions;
+    instructions.fused = (0..code.len())
+        .map(|p| {
+            match_divisor_sum(code, p, ip)
+                .or_else(|| match_divisor_step(code, p, ip))
+                .or_else(|| match_divide(code, p, ip))
+        })
+        .collect();
+
+    instructions
+        .fused
+        .iter()
+        .filter(|fused| fused.is_some())
+        .count()
+}
+
+/// Runs a loop instruction by instruction from `anchor` until it leaves through `exit`.
+#[cfg(test)]
+fn interpret_loop(vm: &mut VM, anchor: usize, exit: usize) {
+    vm.instructions.ip = anchor;
+    while vm.ip() != exit {
+        assert_eq!(vm.tick(), Ok(false), "Halted inside of the loop");
+    }
+}
+
+/// Checks every fused loop in `source` against the interpreter for all combinations of small
+/// values in the registers that it reads.
+#[cfg(test)]
+fn check_fused_loops(source: &str, expected_loops: usize) {
+    let program: Program = source.parse().unwrap();
+    let mut optimized: VM = VM::new(&program);
+    assert_eq!(
+        optimize(&mut optimized.instructions, program.ip_register),
+        expected_loops
+    );
+
+    for (anchor, fused) in optimized.instructions.fused.clone().into_iter().enumerate() {
+        let fused = match fused {
+            Some(fused) => fused,
+            None => continue,
+        };
+        let (inputs, exit) = match fused {
+            Fused::DivisorStep {
+                factor,
+                counter,
+                target,
+                exit,
+                ..
+            } => ([factor, counter, target], exit),
+            Fused::DivisorSum {
+                counter,
+                target,
+                acc,
+                exit,
+                ..
+            } => ([counter, target, acc], exit),
+            Fused::Divide {
+                quotient,
+                dividend,
+                flag,
+                exit,
+                ..
+            } => ([quotient, dividend, flag], exit),
+        };
+
+        for values in
+            (0..13).flat_map(|a| (0..13).flat_map(move |b| (0..13).map(move |c| [a, b, c])))
+        {
+            let mut regs = Registers::new(program.ip_register);
+            for (&reg, &value) in inputs.iter().zip(&values) {
+                regs[reg] = value;
+            }
+            // Scale the dividend up so that the divide loop actually loops
+            if let Fused::Divide { dividend, .. } = fused {
+                regs[dividend] *= 97;
+            }
+
+            let mut expected: VM = VM::new(&program);
+            expected.regs = regs.clone();
+            interpret_loop(&mut expected, anchor, exit);
+
+            let mut actual: VM = VM::new(&program);
+            actual.regs = regs;
+            assert_eq!(fused.exec(&mut actual.regs), Ok(exit));
+            assert_eq!(
+                actual.regs.regs, expected.regs.regs,
+                "{:?} from {:?}",
+                fused, values
+            );
+        }
+    }
+}
+
+#[test]
+fn fused_loops_match_interpreter() {
+    check_fused_loops(include_str!("../input/day19.txt"), 2);
+    check_fused_loops(include_str!("../input/day21.txt"), 1);
+}
+
+#[test]
+fn optimized_programs_run_to_completion() {
+    // Day 19's part 2 sums the divisors of 10,551,370, which takes far too long unoptimized
+    let mut vm: VM = VM::new(&include_str!("../input/day19.txt").parse().unwrap());
+    vm.regs[0] = 1;
+    optimize(&mut vm.instructions, vm.regs.ip_register);
+    vm.run().unwrap();
+    assert_eq!(vm.regs[0], 18_992_484);
+
+    // Day 21's program halts as soon as register 0 matches the first value it checks
+    let program: Program = include_str!("../input/day21.txt").parse().unwrap();
+    let mut expected: VM = VM::new(&program);
+    expected.regs[0] = 10_720_163;
+    let mut optimized = expected.clone();
+    expected.run().unwrap();
+
+    optimize(&mut optimized.instructions, optimized.regs.ip_register);
+    optimized.run().unwrap();
+    assert!(optimized.is_halted());
+    assert_eq!(optimized.regs.regs, expected.regs.regs);
+}
@@ -0,0 +1,108 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+/// A single day's puzzle.  The raw input is parsed once and then handed to both parts.
+pub trait Solution {
+    type Input;
+    type Part1: Display;
+    type Part2: Display;
+
+    /// The puzzle input that is embedded into the binary, used if no other input is provided.
+    const INPUT: &'static str;
+
+    fn parse(input: &str) -> Self::Input;
+
+    fn part1(input: &Self::Input) -> Self::Part1;
+
+    fn part2(input: &Self::Input) -> Self::Part2;
+
+    /// Describes how a part 1 answer came about in more detail than the answer itself, for
+    /// `--report`.  Most days have nothing to add.
+    fn report_part1(_answer: &Self::Part1) -> Option<String> { None }
+
+    /// Like `report_part1`, for part 2
+    fn report_part2(_answer: &Self::Part2) -> Option<String> { None }
+}
+
+/// Which parts of a day to run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parts {
+    Both,
+    Part1,
+    Part2,
+}
+
+impl Parts {
+    pub fn includes(self, part: usize) -> bool {
+        match self {
+            Parts::Both => true,
+            Parts::Part1 => part == 1,
+            Parts::Part2 => part == 2,
+        }
+    }
+}
+
+/// The rendered answers for the parts of a day that were run, along with any reports on them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Answers {
+    pub part1: Option<String>,
+    pub part2: Option<String>,
+    pub part1_report: Option<String>,
+    pub part2_report: Option<String>,
+}
+
+/// How long each step of solving a day took.  Parts that weren't run have no timing.
+#[derive(Clone, Copy, Debug)]
+pub struct Timings {
+    pub parse: Duration,
+    pub part1: Option<Duration>,
+    pub part2: Option<Duration>,
+}
+
+/// A type-erased `solve::<S>`, used to put all of the days into a single table.
+pub type Runner = fn(Option<&str>, Parts) -> (Answers, Timings);
+
+fn run_part<I, T: Display>(
+    parts: Parts,
+    part: usize,
+    input: &I,
+    f: fn(&I) -> T,
+    report: fn(&T) -> Option<String>,
+) -> (Option<String>, Option<String>, Option<Duration>) {
+    if !parts.includes(part) {
+        return (None, None, None);
+    }
+
+    let start = Instant::now();
+    let answer = f(input);
+    let elapsed = start.elapsed();
+
+    (Some(answer.to_string()), report(&answer), Some(elapsed))
+}
+
+/// Solves the selected parts of `S` for `input`, falling back to the embedded input if none is
+/// given.  Rendering the answers is not included in the timings.
+pub fn solve<S: Solution>(input: Option<&str>, parts: Parts) -> (Answers, Timings) {
+    let start = Instant::now();
+    let parsed = S::parse(input.unwrap_or(S::INPUT));
+    let parse = start.elapsed();
+
+    let (part1, part1_report, part1_time) = run_part(parts, 1, &parsed, S::part1, S::report_part1);
+    let (part2, part2_report, part2_time) = run_part(parts, 2, &parsed, S::part2, S::report_part2);
+
+    let answers = Answers {
+        part1,
+        part2,
+        part1_report,
+        part2_report,
+    };
+    let timings = Timings {
+        parse,
+        part1: part1_time,
+        part2: part2_time,
+    };
+
+    (answers, timings)
+}
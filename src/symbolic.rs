@@ -0,0 +1,538 @@
+//! Runs `#ip` programs with some of their registers starting out unknown, to find out which
+//! initial values make the program reach a given instruction.
+//!
+//! Registers hold expressions over the unknown initial values, which are folded down to constants
+//! wherever possible, so everything that doesn't depend on them runs just like in the VM.  When
+//! the instruction pointer ends up depending on them, z3 is asked which values it can take given
+//! the conditions collected along the way, and execution forks into one path for each of them.
+//! Paths are explored in order of the number of instructions they executed, so the first one
+//! that reaches the goal is the shortest.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    fmt::{self, Display, Formatter},
+    mem,
+    rc::Rc,
+};
+
+use z3::{Ast, Config, Context, Solver};
+
+use crate::asm_common::*;
+
+/// The most places a single jump is followed to.  Jumps that can go anywhere else are cut off.
+const MAX_FORKS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    Add,
+    Mul,
+    And,
+    Or,
+    Gt,
+    Eq,
+}
+
+impl Op {
+    fn eval(self, a: usize, b: usize) -> Option<usize> {
+        match self {
+            Op::Add => a.checked_add(b),
+            Op::Mul => a.checked_mul(b),
+            Op::And => Some(a & b),
+            Op::Or => Some(a | b),
+            Op::Gt => Some((a > b) as usize),
+            Op::Eq => Some((a == b) as usize),
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Mul => "*",
+            Op::And => "&",
+            Op::Or => "|",
+            Op::Gt => ">",
+            Op::Eq => "==",
+        }
+    }
+}
+
+/// The value of a register in terms of the initial values of the unknown registers
+#[derive(PartialEq, Eq, Debug)]
+pub enum Expr {
+    Const(usize),
+    /// The initial value of a register
+    Input(usize),
+    Binary(Op, Rc<Expr>, Rc<Expr>),
+}
+
+impl Display for Expr {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(n) => write!(fmt, "{}", n),
+            Expr::Input(reg) => write!(fmt, "r{}", reg),
+            Expr::Binary(op, a, b) => write!(fmt, "({} {} {})", a, op.symbol(), b),
+        }
+    }
+}
+
+impl Expr {
+    fn as_const(&self) -> Option<usize> {
+        match *self {
+            Expr::Const(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Combines two expressions, folding constants and operations that don't do anything.
+    /// Fails if both are constant and the result overflows.
+    fn binary(op: Op, a: &Rc<Expr>, b: &Rc<Expr>) -> Result<Rc<Expr>, VmError> {
+        let constant = |n: usize| Ok(Rc::new(Expr::Const(n)));
+
+        match (op, a.as_const(), b.as_const()) {
+            (_, Some(a), Some(b)) => op.eval(a, b).ok_or(VmError::Overflow).and_then(constant),
+            (Op::Add, Some(0), _) | (Op::Mul, Some(1), _) | (Op::Or, Some(0), _) => Ok(b.clone()),
+            (Op::Add, _, Some(0)) | (Op::Mul, _, Some(1)) | (Op::Or, _, Some(0)) => Ok(a.clone()),
+            (Op::Mul, Some(0), _) | (Op::Mul, _, Some(0)) => constant(0),
+            (Op::And, Some(0), _) | (Op::And, _, Some(0)) => constant(0),
+            (Op::Eq, ..) if a == b => constant(1),
+            (Op::Gt, ..) if a == b => constant(0),
+            _ => Ok(Rc::new(Expr::Binary(op, a.clone(), b.clone()))),
+        }
+    }
+}
+
+/// Where the paths are trying to get to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Goal {
+    /// Be about to execute the instruction at this address
+    Instruction(usize),
+    /// Jump out of the program
+    Halt,
+}
+
+/// The shortest path to a goal that was found
+#[derive(Debug)]
+pub struct Reached {
+    /// The number of instructions executed on the way
+    pub steps: usize,
+    /// Initial values for each unknown register that make the program take this path
+    pub inputs: Vec<(usize, usize)>,
+    /// The conditions on the unknown registers that hold along the path.  Every expression is
+    /// nonzero.
+    pub constraints: Vec<Rc<Expr>>,
+}
+
+#[derive(Clone)]
+struct Path {
+    ip: usize,
+    regs: Vec<Rc<Expr>>,
+    constraints: Vec<Rc<Expr>>,
+    steps: usize,
+}
+
+/// How running a path until something interesting happens ended
+enum Segment {
+    Reached,
+    /// The instruction pointer depends on the unknown registers.
+    Fork(Rc<Expr>),
+    /// The program halted without reaching the goal, failed or ran out of steps.
+    Dead,
+}
+
+/// A path waiting to be explored further, or one that has reached the goal.  Ordered so that the
+/// one with the fewest steps comes out of a `BinaryHeap` first, then the oldest.
+struct Queued {
+    path: Path,
+    reached: bool,
+    seq: usize,
+}
+
+impl Queued {
+    fn key(&self) -> (usize, usize) { (self.path.steps, self.seq) }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool { self.key() == other.key() }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering { other.key().cmp(&self.key()) }
+}
+
+/// Translates expressions into z3's integer arithmetic.  Bitwise operations on values that
+/// aren't constant are done on their individual bits, which are defined the first time they're
+/// needed.
+struct Encoder<'ctx> {
+    ctx: &'ctx Context,
+    solver: Solver<'ctx>,
+    inputs: HashMap<usize, Ast<'ctx>>,
+    /// Keyed by address; the expressions are kept alive so that addresses aren't reused
+    nodes: HashMap<*const Expr, (Rc<Expr>, Ast<'ctx>)>,
+    bits: HashMap<*const Expr, Vec<Ast<'ctx>>>,
+}
+
+fn word_bits() -> usize { mem::size_of::<usize>() * 8 }
+
+impl<'ctx> Encoder<'ctx> {
+    fn new(ctx: &'ctx Context) -> Self {
+        Encoder {
+            ctx,
+            solver: Solver::new(ctx),
+            inputs: HashMap::new(),
+            nodes: HashMap::new(),
+            bits: HashMap::new(),
+        }
+    }
+
+    fn constant(&self, n: usize) -> Ast<'ctx> { self.ctx.from_u64(n as u64) }
+
+    fn input(&mut self, reg: usize) -> Ast<'ctx> {
+        if let Some(input) = self.inputs.get(&reg) {
+            return input.clone();
+        }
+
+        let input = self.ctx.named_int_const(&format!("r{}", reg));
+        self.solver.assert(&input.ge(&self.constant(0)));
+        self.solver
+            .assert(&input.le(&self.constant(usize::max_value())));
+        self.inputs.insert(reg, input.clone());
+        input
+    }
+
+    fn encode(&mut self, expr: &Rc<Expr>) -> Ast<'ctx> {
+        let key = &**expr as *const Expr;
+        if let Some((_, ast)) = self.nodes.get(&key) {
+            return ast.clone();
+        }
+
+        let (zero, one) = (self.constant(0), self.constant(1));
+        let ast = match **expr {
+            Expr::Const(n) => self.constant(n),
+            Expr::Input(reg) => self.input(reg),
+            Expr::Binary(op @ Op::And, ref a, ref b) | Expr::Binary(op @ Op::Or, ref a, ref b) => {
+                let (a, b) = (self.bits(a), self.bits(b));
+                let terms: Vec<Ast> = (0..word_bits())
+                    .map(|i| {
+                        let bit = if op == Op::And {
+                            a[i].and(&[&b[i]])
+                        } else {
+                            a[i].or(&[&b[i]])
+                        };
+                        bit.ite(&self.ctx.from_u64(1 << i), &zero)
+                    })
+                    .collect();
+                zero.add(&terms.iter().collect::<Vec<_>>())
+            },
+            Expr::Binary(op, ref a, ref b) => {
+                let (a, b) = (self.encode(a), self.encode(b));
+                match op {
+                    Op::Add => a.add(&[&b]),
+                    Op::Mul => a.mul(&[&b]),
+                    Op::Gt => a.gt(&b).ite(&one, &zero),
+                    Op::Eq => a._eq(&b).ite(&one, &zero),
+                    Op::And | Op::Or => unreachable!(),
+                }
+            },
+        };
+
+        self.nodes.insert(key, (expr.clone(), ast.clone()));
+        ast
+    }
+
+    /// The bits of `expr`, least significant first.  They're only tied to its value when it fits
+    /// in a word, which it does on every path that doesn't overflow.
+    fn bits(&mut self, expr: &Rc<Expr>) -> Vec<Ast<'ctx>> {
+        if let Some(n) = expr.as_const() {
+            return (0..word_bits())
+                .map(|i| self.ctx.from_bool(n >> i & 1 == 1))
+                .collect();
+        }
+
+        let key = &**expr as *const Expr;
+        if let Some(bits) = self.bits.get(&key) {
+            return bits.clone();
+        }
+
+        let value = self.encode(expr);
+        let zero = self.constant(0);
+        let bits: Vec<Ast> = (0..word_bits())
+            .map(|_| self.ctx.fresh_bool_const("bit"))
+            .collect();
+        let terms: Vec<Ast> = bits
+            .iter()
+            .enumerate()
+            .map(|(i, bit)| bit.ite(&self.ctx.from_u64(1 << i), &zero))
+            .collect();
+        let sum = zero.add(&terms.iter().collect::<Vec<_>>());
+        let fits = value.le(&self.constant(usize::max_value()));
+        self.solver.assert(&fits.implies(&value._eq(&sum)));
+
+        self.bits.insert(key, bits.clone());
+        bits
+    }
+
+    /// Asserts that every constraint is nonzero until the matching `pop`.
+    fn push_constraints(&mut self, constraints: &[Rc<Expr>]) {
+        let encoded: Vec<Ast> = constraints.iter().map(|c| self.encode(c)).collect();
+        let zero = self.constant(0);
+
+        self.solver.push();
+        for constraint in encoded {
+            self.solver.assert(&constraint._eq(&zero).not());
+        }
+    }
+
+    /// Finds up to `MAX_FORKS` values that `expr` can take on while `constraints` hold.
+    fn feasible_values(&mut self, constraints: &[Rc<Expr>], expr: &Rc<Expr>) -> Vec<usize> {
+        let target = self.encode(expr);
+        self.push_constraints(constraints);
+
+        let mut values = Vec::new();
+        while values.len() < MAX_FORKS && self.solver.check() {
+            let value = self
+                .solver
+                .get_model()
+                .eval(&target)
+                .and_then(|value| value.as_u64())
+                .expect("z3 didn't give a value for the instruction pointer");
+            values.push(value as usize);
+            self.solver
+                .assert(&target._eq(&self.constant(value as usize)).not());
+        }
+
+        self.solver.pop(1);
+        values
+    }
+
+    /// Finds values for the unknown registers that make `constraints` hold.
+    fn solve_inputs(
+        &mut self,
+        constraints: &[Rc<Expr>],
+        registers: &[usize],
+    ) -> Vec<(usize, usize)> {
+        let inputs: Vec<Ast> = registers.iter().map(|&reg| self.input(reg)).collect();
+        self.push_constraints(constraints);
+        assert!(
+            self.solver.check(),
+            "The constraints of a path were unsatisfiable"
+        );
+
+        let model = self.solver.get_model();
+        let values = registers
+            .iter()
+            .zip(&inputs)
+            .map(|(&reg, input)| {
+                let value = model
+                    .eval(input)
+                    .and_then(|value| value.as_u64())
+                    .expect("z3 didn't give a value for an input");
+                (reg, value as usize)
+            })
+            .collect();
+
+        self.solver.pop(1);
+        values
+    }
+}
+
+pub struct SymbolicExecutor<'a> {
+    program: &'a Program,
+    symbolic: Vec<usize>,
+}
+
+impl<'a> SymbolicExecutor<'a> {
+    /// Prepares to run `program` with the registers in `symbolic` starting out unknown and all
+    /// others starting out as zero.
+    pub fn new(program: &'a Program, symbolic: &[usize]) -> Self {
+        SymbolicExecutor {
+            program,
+            symbolic: symbolic.to_vec(),
+        }
+    }
+
+    /// Runs `path` until it reaches the goal, needs to fork or can't go on.
+    fn run_segment(&self, path: &mut Path, goal: Goal, max_steps: usize) -> Segment {
+        let constant = |n: usize| Rc::new(Expr::Const(n));
+        let max_word = constant(usize::max_value());
+
+        loop {
+            let instr = match self.program.instructions.get(path.ip) {
+                _ if goal == Goal::Instruction(path.ip) => return Segment::Reached,
+                Some(&instr) => instr,
+                None if goal == Goal::Halt => return Segment::Reached,
+                None => return Segment::Dead,
+            };
+            if path.steps == max_steps {
+                return Segment::Dead;
+            }
+
+            if let Some(ip_register) = self.program.ip_register {
+                path.regs[ip_register] = constant(path.ip);
+            }
+            let (kind1, kind2) = instr.opcode.operands();
+            let operand = |n: usize, kind: Operand| match kind {
+                Operand::Register => path.regs[n].clone(),
+                Operand::Immediate | Operand::Ignored => constant(n),
+            };
+            let (a, b) = (operand(instr.in1, kind1), operand(instr.in2, kind2));
+
+            let op = match instr.opcode {
+                Opcode::Addr | Opcode::Addi => Some(Op::Add),
+                Opcode::Mulr | Opcode::Muli => Some(Op::Mul),
+                Opcode::Banr | Opcode::Bani => Some(Op::And),
+                Opcode::Borr | Opcode::Bori => Some(Op::Or),
+                Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => Some(Op::Gt),
+                Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => Some(Op::Eq),
+                Opcode::Setr | Opcode::Seti => None,
+            };
+            let value = match op {
+                Some(op) => Expr::binary(op, &a, &b),
+                None => Ok(a),
+            };
+            let value = match value {
+                Ok(value) => value,
+                Err(_) => return Segment::Dead,
+            };
+
+            // Symbolic results that overflow would have stopped the VM
+            let may_overflow = op == Some(Op::Add) || op == Some(Op::Mul);
+            if may_overflow && value.as_const().is_none() {
+                let overflows = Expr::binary(Op::Gt, &value, &max_word).unwrap();
+                path.constraints
+                    .push(Expr::binary(Op::Eq, &overflows, &constant(0)).unwrap());
+            }
+            path.regs[instr.out] = value;
+            path.steps += 1;
+
+            path.ip = match self.program.ip_register {
+                Some(ip_register) => match path.regs[ip_register].as_const() {
+                    Some(ip) => ip.saturating_add(1),
+                    None => return Segment::Fork(path.regs[ip_register].clone()),
+                },
+                None => path.ip + 1,
+            };
+        }
+    }
+
+    /// Searches for the path that reaches `goal` while executing the fewest instructions, giving
+    /// up on paths that take more than `max_steps`.  Returns `None` if there isn't one.
+    pub fn solve(&self, goal: Goal, max_steps: usize) -> Option<Reached> {
+        let cfg = Config::new();
+        let ctx = Context::new(&cfg);
+        let mut encoder = Encoder::new(&ctx);
+
+        let regs = (0..REGISTER_COUNT)
+            .map(|reg| {
+                Rc::new(if self.symbolic.contains(&reg) {
+                    Expr::Input(reg)
+                } else {
+                    Expr::Const(0)
+                })
+            })
+            .collect();
+        let mut queue = BinaryHeap::new();
+        let mut seq = 0;
+        queue.push(Queued {
+            path: Path {
+                ip: 0,
+                regs,
+                constraints: Vec::new(),
+                steps: 0,
+            },
+            reached: false,
+            seq,
+        });
+
+        while let Some(Queued {
+            mut path, reached, ..
+        }) = queue.pop()
+        {
+            if reached {
+                return Some(Reached {
+                    steps: path.steps,
+                    inputs: encoder.solve_inputs(&path.constraints, &self.symbolic),
+                    constraints: path.constraints,
+                });
+            }
+
+            match self.run_segment(&mut path, goal, max_steps) {
+                Segment::Reached => {
+                    seq += 1;
+                    queue.push(Queued {
+                        path,
+                        reached: true,
+                        seq,
+                    });
+                },
+                Segment::Fork(target) => {
+                    let ip_register = self.program.ip_register.unwrap();
+                    for value in encoder.feasible_values(&path.constraints, &target) {
+                        let taken = Rc::new(Expr::Const(value));
+                        let mut child = path.clone();
+                        child
+                            .constraints
+                            .push(Expr::binary(Op::Eq, &target, &taken).unwrap());
+                        child.regs[ip_register] = taken;
+                        child.ip = value.saturating_add(1);
+
+                        seq += 1;
+                        queue.push(Queued {
+                            path: child,
+                            reached: false,
+                            seq,
+                        });
+                    }
+                },
+                Segment::Dead => (),
+            }
+        }
+
+        None
+    }
+}
+
+#[test]
+fn reach_instruction() {
+    // Only reaches the last instruction if `(r0 * 3 + 7) & 0xff == 22` and `r1 > 2`
+    let program: Program = "#ip 4\nmuli 0 3 2\naddi 2 7 2\nbani 2 255 2\neqri 2 22 2\naddr 2 4 \
+                            4\nseti 99 0 4\ngtri 1 2 3\naddr 3 4 4\nseti 99 0 4\nseti 1 0 5\n"
+        .parse()
+        .unwrap();
+
+    let reached = SymbolicExecutor::new(&program, &[0, 1])
+        .solve(Goal::Instruction(9), 100)
+        .unwrap();
+    assert_eq!(reached.steps, 7);
+
+    let (r0, r1) = (reached.inputs[0].1, reached.inputs[1].1);
+    assert_eq!(reached.inputs[0].0, 0);
+    assert_eq!(r0.wrapping_mul(3).wrapping_add(7) & 0xff, 22);
+    assert!(r1 > 2);
+
+    let mut vm: VM = VM::new(&program);
+    vm.regs[0] = r0;
+    vm.regs[1] = r1;
+    assert_eq!(vm.run_with_limit(100), Ok(8));
+    assert_eq!(vm.regs[5], 1);
+
+    assert!(SymbolicExecutor::new(&program, &[])
+        .solve(Goal::Instruction(9), 100)
+        .is_none());
+}
+
+#[test]
+fn day21_soonest_halt() {
+    let program: Program = include_str!("../input/day21.txt").parse().unwrap();
+    let reached = SymbolicExecutor::new(&program, &[0])
+        .solve(Goal::Halt, 1_000_000)
+        .unwrap();
+
+    assert_eq!(reached.inputs, vec![(0, 10_720_163)]);
+}
@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use crate::solution::Timings;
+
+/// Summary of a set of duration samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    /// Summarizes `samples`, which must not be empty.  The median of an even number of samples is
+    /// the lower of the two middle ones.
+    pub fn from_samples(mut samples: Vec<Duration>) -> Self {
+        assert!(!samples.is_empty(), "Can't summarize zero samples");
+        samples.sort();
+
+        Stats {
+            min: samples[0],
+            median: samples[(samples.len() - 1) / 2],
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+/// Timing statistics for each step of a single day.  Parts that weren't run have no statistics.
+pub struct DayReport {
+    pub day: usize,
+    pub iterations: usize,
+    pub parse: Stats,
+    pub part1: Option<Stats>,
+    pub part2: Option<Stats>,
+}
+
+impl DayReport {
+    pub fn new(day: usize, samples: &[Timings]) -> Self {
+        let part_stats = |step: fn(&Timings) -> Option<Duration>| {
+            samples
+                .iter()
+                .map(step)
+                .collect::<Option<Vec<_>>>()
+                .map(Stats::from_samples)
+        };
+
+        DayReport {
+            day,
+            iterations: samples.len(),
+            parse: Stats::from_samples(samples.iter().map(|t| t.parse).collect()),
+            part1: part_stats(|t| t.part1),
+            part2: part_stats(|t| t.part2),
+        }
+    }
+
+    fn steps(&self) -> Vec<(&'static str, &Stats)> {
+        let mut steps = vec![("parse", &self.parse)];
+        steps.extend(self.part1.as_ref().map(|stats| ("part1", stats)));
+        steps.extend(self.part2.as_ref().map(|stats| ("part2", stats)));
+        steps
+    }
+}
+
+/// Renders a duration with a unit that keeps it readable, e.g. `12.34 ms`.
+pub fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_secs() as f64 * 1e9 + f64::from(duration.subsec_nanos());
+
+    if nanos < 1e3 {
+        format!("{} ns", nanos)
+    } else if nanos < 1e6 {
+        format!("{:.2} µs", nanos / 1e3)
+    } else if nanos < 1e9 {
+        format!("{:.2} ms", nanos / 1e6)
+    } else {
+        format!("{:.2} s", nanos / 1e9)
+    }
+}
+
+pub fn print_table_header() {
+    println!(
+        "{:>3}  {:<5}  {:>10}  {:>10}  {:>10}",
+        "day", "step", "min", "median", "max"
+    );
+}
+
+/// Prints one row per step of `report`.
+pub fn print_table_rows(report: &DayReport) {
+    for (step, stats) in report.steps() {
+        println!(
+            "{:>3}  {:<5}  {:>10}  {:>10}  {:>10}",
+            report.day,
+            step,
+            format_duration(stats.min),
+            format_duration(stats.median),
+            format_duration(stats.max)
+        );
+    }
+}
+
+fn duration_nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos())
+}
+
+/// Renders the reports as a JSON array with one object per day.  Parts that weren't run are left
+/// out.  All durations are in nanoseconds, e.g. `{"day": 1, "iterations": 5, "parse": {"min": 1,
+/// "median": 2, "max": 3}, ...}`.
+pub fn to_json(reports: &[DayReport]) -> String {
+    let days: Vec<String> = reports
+        .iter()
+        .map(|report| {
+            let steps: Vec<String> = report
+                .steps()
+                .into_iter()
+                .map(|(step, stats)| {
+                    format!(
+                        "\"{}\": {{\"min\": {}, \"median\": {}, \"max\": {}}}",
+                        step,
+                        duration_nanos(stats.min),
+                        duration_nanos(stats.median),
+                        duration_nanos(stats.max)
+                    )
+                })
+                .collect();
+
+            format!(
+                "  {{\"day\": {}, \"iterations\": {}, {}}}",
+                report.day,
+                report.iterations,
+                steps.join(", ")
+            )
+        })
+        .collect();
+
+    format!("[\n{}\n]", days.join(",\n"))
+}
+
+#[test]
+fn timing_report() {
+    let ms = Duration::from_millis;
+    let samples = [(3, 10, 7), (1, 30, 8), (2, 20, 9), (4, 40, 6)]
+        .iter()
+        .map(|&(parse, part1, part2)| Timings {
+            parse: ms(parse),
+            part1: Some(ms(part1)),
+            part2: Some(ms(part2)),
+        })
+        .collect::<Vec<_>>();
+    let report = DayReport::new(3, &samples);
+
+    assert_eq!(report.parse, Stats {
+        min: ms(1),
+        median: ms(2),
+        max: ms(4)
+    });
+    assert_eq!(
+        report.part1,
+        Some(Stats {
+            min: ms(10),
+            median: ms(20),
+            max: ms(40)
+        })
+    );
+    assert_eq!(
+        to_json(&[report]),
+        "[\n  {\"day\": 3, \"iterations\": 4, \"parse\": {\"min\": 1000000, \"median\": 2000000, \
+         \"max\": 4000000}, \"part1\": {\"min\": 10000000, \"median\": 20000000, \"max\": \
+         40000000}, \"part2\": {\"min\": 6000000, \"median\": 7000000, \"max\": 9000000}}\n]"
+    );
+    assert_eq!(format_duration(Duration::from_micros(1500)), "1.50 ms");
+}
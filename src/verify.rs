@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use crate::solution::Answers;
+
+/// The expected answers for the embedded inputs
+pub const ANSWERS: &str = include_str!("../answers.txt");
+
+/// Known-good answers keyed by `(day, part)`.
+pub struct ExpectedAnswers(HashMap<(usize, usize), String>);
+
+/// Escapes newlines and backslashes so that any answer fits onto a single line.
+pub fn escape(answer: &str) -> String { answer.replace('\\', "\\\\").replace('\n', "\\n") }
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+impl ExpectedAnswers {
+    /// Parses an answers file.  Each line has the form `<day> <part> <answer>` with the answer
+    /// escaped as by `escape`.  Blank lines and lines starting with `#` are ignored.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut answers = HashMap::new();
+
+        for (line_ix, line) in s.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.splitn(3, ' ');
+            let mut parse_field = |name: &str| -> Result<usize, String> {
+                fields
+                    .next()
+                    .and_then(|field| field.parse().ok())
+                    .ok_or_else(|| format!("Line {}: missing or invalid {}", line_ix + 1, name))
+            };
+            let day = parse_field("day")?;
+            let part = parse_field("part")?;
+            let answer = fields
+                .next()
+                .ok_or_else(|| format!("Line {}: missing answer", line_ix + 1))?;
+
+            answers.insert((day, part), unescape(answer));
+        }
+
+        Ok(ExpectedAnswers(answers))
+    }
+
+    pub fn get(&self, day: usize, part: usize) -> Option<&str> {
+        self.0.get(&(day, part)).map(String::as_str)
+    }
+}
+
+/// Compares the parts of `day` that were run with their expected answers, printing the outcome of
+/// each.  Returns the number of parts with incorrect answers.
+pub fn report(expected: &ExpectedAnswers, day: usize, answers: &Answers) -> usize {
+    let mut failures = 0;
+
+    for (part, answer) in [&answers.part1, &answers.part2].iter().enumerate() {
+        let part = part + 1;
+        let answer = match answer {
+            Some(answer) => answer,
+            None => continue,
+        };
+
+        match expected.get(day, part) {
+            Some(expected) if expected == answer.as_str() => println!("Part {}: ok", part),
+            Some(expected) => {
+                failures += 1;
+                println!(
+                    "Part {}: FAILED (expected {}, got {})",
+                    part,
+                    escape(expected),
+                    escape(answer)
+                );
+            },
+            None => println!("Part {}: no expected answer (got {})", part, escape(answer)),
+        }
+    }
+
+    failures
+}
+
+#[test]
+fn answers_file_parsing() {
+    let answer = "#..#\n\\#..";
+    let file = format!("# comment\n\n1 2 73272\n10 1 {}\n", escape(answer));
+    let expected = ExpectedAnswers::parse(&file).unwrap();
+
+    assert_eq!(expected.get(1, 2), Some("73272"));
+    assert_eq!(expected.get(10, 1), Some(answer));
+    assert_eq!(expected.get(1, 1), None);
+    assert!(ExpectedAnswers::parse("1 x 2").is_err());
+}